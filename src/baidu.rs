@@ -5,11 +5,25 @@ use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use serde::Deserialize;
 
 use crate::{
+    capability::{unsupported, Auth, Capabilities},
     err::{ErrInfoBuildle, TransError},
     DomainType, Language, Translation,
 };
 
 const BAIDU_API: &str = "https://fanyi-api.baidu.com/api/trans/vip/fieldtranslate";
+/// 垂直领域翻译新增的领域标识，使用`DomainType::Custom`传递:
+/// 信息技术、机械制造、生物医药、学术论文、航空航天、人文社科、新闻资讯、法律法规、合同
+pub const BAIDU_DOMAINS: [&str; 9] = [
+    "it",
+    "machinery",
+    "senimed",
+    "academic",
+    "aerospace",
+    "wiki",
+    "news",
+    "law",
+    "contract",
+];
 type Result<T> = std::result::Result<T, TransError>;
 
 #[derive(Debug, Deserialize)]
@@ -23,17 +37,65 @@ pub struct ReturnResult {
     pub src: String,
     pub dst: String,
 }
+/// 接口返回的错误
+#[derive(Debug, Deserialize)]
+pub struct BaiduErr {
+    /// 错误码，接口有时返回字符串，有时返回数字
+    pub error_code: serde_json::Value,
+    pub error_msg: Option<String>,
+}
+impl BaiduErr {
+    pub fn code(&self) -> String {
+        match &self.error_code {
+            serde_json::Value::String(s) => s.clone(),
+            v => v.to_string(),
+        }
+    }
+}
 impl Display for BaiduRes {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for v in &self.trans_result {
-            write!(f, "{}", v.dst);
+            write!(f, "{}", v.dst)?;
         }
         Ok(())
     }
 }
 
+/// 本地参数检查(长度、原文、appid和秘钥)失败时错误的`METHOD`，见`TransError::is_client_error`
+pub(crate) const CHECK: &str = "check()";
+/// 接口返回成功时的错误码
+const SUCCESS: &str = "52000";
+
+/// 解析接口返回。有`trans_result`且没有错误码(或为`52000`)时成功，
+/// 出错时返回 {"error_code":"54004","error_msg":"..."}
+fn parse_response(body: &str, request: &HashMap<&str, String>) -> Result<BaiduRes> {
+    let res = serde_json::from_str::<BaiduRes>(body);
+    let err = serde_json::from_str::<BaiduErr>(body)
+        .ok()
+        .filter(|err| err.code() != SUCCESS);
+    match (res, err) {
+        (Ok(res), None) => Ok(res),
+        (_, Some(err)) => Err(TransError::BaiduError(
+            ErrInfoBuildle::new()
+                .model("Baidu")
+                .method("trans() res")
+                .code(&err.code())
+                .original(err.error_msg.unwrap_or_default())
+                .build(),
+        )),
+        (Err(e), None) => Err(TransError::BaiduError(
+            ErrInfoBuildle::new()
+                .model("Baidu")
+                .method("trans() res")
+                .original(e.to_string())
+                .data(serde_json::to_string(request).unwrap())
+                .build(),
+        )),
+    }
+}
+
 pub struct Baidu<'a> {
-    words: &'a str,
+    words: String,
     appid: &'a str,
     // sign: String,
     from: Language,
@@ -41,22 +103,23 @@ pub struct Baidu<'a> {
     max_len: usize,
     domain: DomainType,
     secret_key: &'a str,
+    endpoint: String,
 }
 
 impl<'a> Translation<'a> for Baidu<'a> {
-    fn set_words(&mut self, words: &'a str) {
-        self.words = words;
+    fn set_words(&mut self, words: &str) {
+        self.words = words.to_owned();
     }
 
-    fn get_words(&self) -> &'a str {
-        self.words
+    fn get_words(&self) -> &str {
+        &self.words
     }
 
     fn trans(&mut self) -> Result<String> {
         self.check()?;
         let map = self.get_data();
         let client = reqwest::blocking::Client::new();
-        let request = client.post(BAIDU_API).form(&map);
+        let request = client.post(&self.endpoint).form(&map);
 
         let response = request.send().map_err(|e| {
             TransError::RequestError(
@@ -67,30 +130,39 @@ impl<'a> Translation<'a> for Baidu<'a> {
                     .build(),
             )
         })?;
-        // let status = response.status();
-        // let res = response.text();
-        // println!("Res:{:?}", res);
-        // Ok("".to_owned())
-        // let a = ;
-        let res = response.json::<BaiduRes>().map_err(|e| {
-            TransError::BaiduError(
+        let body = response.text().map_err(|e| {
+            TransError::RequestError(
                 ErrInfoBuildle::new()
-                    .model("Baidu")
-                    .method("trans() res")
+                    .model("BAIDU")
+                    .method("trans() response")
                     .original(e.to_string())
-                    .data(serde_json::to_string(&map).unwrap())
                     .build(),
             )
         })?;
+        let res = parse_response(&body, &map)?;
         Ok(res.to_string())
     }
 
-    fn set_from(&mut self, language: Language) {
-        self.from = language
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        if !self.capabilities().supports_from(language) {
+            return Err(unsupported(
+                "Baidu",
+                format!("不支持的源语言: {}", language),
+            ));
+        }
+        self.from = language;
+        Ok(())
     }
 
-    fn set_to(&mut self, language: Language) {
-        self.to = language
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        if !self.capabilities().supports_to(language) {
+            return Err(unsupported(
+                "Baidu",
+                format!("不支持的目标语言: {}", language),
+            ));
+        }
+        self.to = language;
+        Ok(())
     }
 
     fn set_appid(&mut self, appid: &'a str) {
@@ -101,12 +173,52 @@ impl<'a> Translation<'a> for Baidu<'a> {
         self.secret_key = secret_key
     }
 
-    fn set_domain(&mut self, domain: DomainType) {
-        self.domain = domain
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        if !self.capabilities().supports_domain(&domain) {
+            return Err(unsupported("Baidu", format!("不支持的领域: {}", domain)));
+        }
+        self.domain = domain;
+        Ok(())
+    }
+
+    fn provider(&self) -> &'static str {
+        "Baidu"
+    }
+
+    /// 垂直领域翻译只支持中英互译。领域为`DomainType`的各项和`BAIDU_DOMAINS`
+    fn capabilities(&self) -> Capabilities {
+        let mut domains = vec![
+            DomainType::General,
+            DomainType::Electronics,
+            DomainType::Finance,
+            DomainType::Mechanics,
+            DomainType::Medicine,
+            DomainType::Novel,
+        ];
+        domains.extend(
+            BAIDU_DOMAINS
+                .iter()
+                .map(|id| DomainType::Custom((*id).to_owned())),
+        );
+        Capabilities {
+            pairs: Some(vec![
+                (Language::Zh, Language::En),
+                (Language::En, Language::Zh),
+                (Language::Auto, Language::Zh),
+                (Language::Auto, Language::En),
+            ]),
+            domains: Some(domains),
+            max_length: self.max_len,
+            batch: false,
+            auth: Auth::AppIdAndSecretKey,
+        }
     }
 
     fn build(&self) -> Box<dyn Translation<'a> + 'a> {
-        Box::new(Baidu::new())
+        Box::new(Baidu {
+            endpoint: self.endpoint.clone(),
+            ..Baidu::new()
+        })
     }
 
     fn from(&self) -> Language {
@@ -126,30 +238,44 @@ impl<'a> Translation<'a> for Baidu<'a> {
     }
 
     fn domain(&self) -> DomainType {
-        self.domain
+        self.domain.clone()
     }
 
     fn set_max_length(&mut self, l: usize) {
         self.max_len = l;
     }
 
-    fn max_length(&mut self) -> usize {
+    fn max_length(&self) -> usize {
         self.max_len
     }
 }
 
+impl<'a> Default for Baidu<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Baidu<'a> {
     pub fn new() -> Baidu<'a> {
         Baidu {
-            words: "",
+            words: String::new(),
             appid: "",
             from: Language::Auto,
             to: Language::Zh,
             domain: DomainType::Medicine,
             secret_key: "",
             max_len: 1500,
+            endpoint: BAIDU_API.to_owned(),
         }
     }
+    /// 接口地址，默认为百度垂直领域翻译的地址。用于代理或私有部署
+    pub fn set_endpoint(&mut self, endpoint: &str) {
+        self.endpoint = endpoint.to_owned();
+    }
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
     fn salt(&self) -> String {
         thread_rng()
             .sample_iter(&Alphanumeric)
@@ -165,7 +291,7 @@ impl<'a> Baidu<'a> {
             self.appid,
             self.words,
             salt,
-            Into::<&str>::into(self.domain),
+            Into::<&str>::into(&self.domain),
             self.secret_key
         );
         let mut hasher = Md5::new();
@@ -180,43 +306,88 @@ impl<'a> Baidu<'a> {
         map.insert("appid", self.appid.to_owned());
         map.insert("sign", sign);
         map.insert("salt", salt);
-        map.insert("domain", Into::<&str>::into(self.domain).to_owned());
+        map.insert("domain", Into::<&str>::into(&self.domain).to_owned());
         map.insert("from", Into::<&str>::into(self.from).to_owned());
         map.insert("to", Into::<&str>::into(self.to).to_owned());
         map
     }
     fn check(&self) -> Result<()> {
+        // 中译中、英译英
+        self.capabilities()
+            .check("Baidu", self.from, self.to, &self.domain)?;
         let l = self.words.len();
         if l > self.max_len {
             return Err(TransError::BaiduError(
                 ErrInfoBuildle::new()
                     .model("Baidu")
-                    .method("check len")
+                    .method(CHECK)
                     .original(format!("字符太长了,Max:{}", self.max_len))
                     .build(),
             ));
         }
 
-        if l <= 0 {
+        if l == 0 {
             return Err(TransError::BaiduError(
                 ErrInfoBuildle::new()
                     .model("Baidu")
-                    .method("check len")
-                    .original(format!("字符words未设置了"))
+                    .method(CHECK)
+                    .original("字符words未设置了".to_owned())
                     .build(),
             ));
         }
 
-        if self.appid().len() <= 0 || self.secret_key().len() <= 0 {
+        if self.appid().is_empty() || self.secret_key().is_empty() {
             return Err(TransError::BaiduError(
                 ErrInfoBuildle::new()
                     .model("Baidu")
-                    .method("check")
-                    .original(format!("未设置必要的Appid和秘钥：请调用baidu.set_appid(X) & baidu.set_secret_key(X)"))
+                    .method(CHECK)
+                    .original("未设置必要的Appid和秘钥：请调用baidu.set_appid(X) & baidu.set_secret_key(X)".to_owned())
                     .build(),
             ));
         }
 
-        return Ok(());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: &str) -> Result<String> {
+        parse_response(body, &HashMap::new()).map(|res| res.to_string())
+    }
+
+    #[test]
+    fn parse_responses() {
+        let ok = r#"{"from":"en","to":"zh","trans_result":[{"src":"Hello.","dst":"你好。"},{"src":"World.","dst":"世界。"}]}"#;
+        assert_eq!(parse(ok).unwrap(), "你好。世界。");
+        // 成功时也可能带有错误码52000
+        for code in [r#""52000""#, "52000"] {
+            let body = format!(
+                r#"{{"error_code":{},"from":"en","to":"zh","trans_result":[{{"src":"Hi","dst":"嗨"}}]}}"#,
+                code
+            );
+            assert_eq!(parse(&body).unwrap(), "嗨");
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for body in [
+            r#"{"error_code":"54004","error_msg":"Please recharge"}"#,
+            r#"{"error_code":54004,"error_msg":"Please recharge"}"#,
+            r#"{"error_code":"54004","error_msg":"Please recharge","from":"en","to":"zh","trans_result":[]}"#,
+        ] {
+            let err = parse(body).unwrap_err();
+            assert!(matches!(err, TransError::BaiduError(_)), "{}", body);
+            assert_eq!(err.code(), Some("54004"), "{}", body);
+        }
+        // 错误码为52000但没有译文
+        let err = parse(r#"{"error_code":"52000","error_msg":"success"}"#).unwrap_err();
+        assert!(matches!(err, TransError::BaiduError(_)));
+        let err = parse("<html>").unwrap_err();
+        assert!(matches!(err, TransError::BaiduError(_)));
+        assert_eq!(err.code(), None);
     }
 }
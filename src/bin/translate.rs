@@ -0,0 +1,6 @@
+//! 命令行翻译工具，见`translation_api::cli`
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    translation_api::cli::run(std::env::args_os())
+}
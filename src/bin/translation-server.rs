@@ -0,0 +1,6 @@
+//! 翻译HTTP服务，见`translation_api::server`
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    translation_api::server::run(std::env::args_os())
+}
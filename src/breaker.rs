@@ -0,0 +1,357 @@
+//! 熔断
+//!
+//! 接口持续失败时(如CNKI更换了加密方式)，每个请求都要等到超时才失败。
+//! 最近一段调用的失败比例超过阈值后熔断，冷却期内直接返回`TransError::CircuitOpen`，不再调用接口
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 熔断状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// 正常调用
+    Closed,
+    /// 熔断中，直接返回错误
+    Open,
+    /// 冷却期已过，放行一次试探调用。成功则恢复，失败则重新熔断
+    HalfOpen,
+}
+
+struct Breaker {
+    state: CircuitState,
+    /// 最近的调用结果，true为失败
+    window: VecDeque<bool>,
+    opened_at: Instant,
+    probing: bool,
+}
+
+/// 带熔断的翻译器
+/// `build()`/`fork()`出的翻译器与原翻译器共用熔断状态
+pub struct CircuitBreaker<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    breaker: Arc<Mutex<Breaker>>,
+    window: usize,
+    min_calls: usize,
+    failure_ratio: f64,
+    cooldown: Duration,
+}
+
+impl<'a> CircuitBreaker<'a> {
+    /// 默认: 最近20次调用中至少5次、且失败比例达到50%时熔断，冷却30秒
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>) -> Self {
+        CircuitBreaker {
+            inner,
+            breaker: Arc::new(Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                window: VecDeque::new(),
+                opened_at: Instant::now(),
+                probing: false,
+            })),
+            window: 20,
+            min_calls: 5,
+            failure_ratio: 0.5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+    /// 统计最近多少次调用
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+    /// 统计的调用次数达到多少次后才判断是否熔断
+    pub fn min_calls(mut self, min_calls: usize) -> Self {
+        self.min_calls = min_calls.max(1);
+        self
+    }
+    /// 失败比例达到多少时熔断，取值范围(0, 1]。
+    /// 超出范围时取最接近的有效值: 不大于0视为有失败即熔断，大于1或NaN视为全部失败才熔断
+    pub fn failure_ratio(mut self, ratio: f64) -> Self {
+        self.failure_ratio = if ratio.is_nan() {
+            1.0
+        } else {
+            ratio.clamp(f64::MIN_POSITIVE, 1.0)
+        };
+        self
+    }
+    /// 熔断后多久放行试探调用
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+    pub fn state(&self) -> CircuitState {
+        let breaker = self.breaker();
+        if breaker.state == CircuitState::Open && breaker.opened_at.elapsed() >= self.cooldown {
+            CircuitState::HalfOpen
+        } else {
+            breaker.state
+        }
+    }
+    /// 手动恢复为正常状态
+    pub fn reset(&self) {
+        let mut breaker = self.breaker();
+        breaker.state = CircuitState::Closed;
+        breaker.window.clear();
+        breaker.probing = false;
+    }
+    /// 调用前检查。返回是否为试探调用
+    fn admit(&self) -> Result<bool> {
+        let mut breaker = self.breaker();
+        if breaker.state == CircuitState::Open {
+            let elapsed = breaker.opened_at.elapsed();
+            if elapsed < self.cooldown {
+                return Err(self.open_err(self.cooldown - elapsed));
+            }
+            breaker.state = CircuitState::HalfOpen;
+        }
+        if breaker.state == CircuitState::HalfOpen {
+            // 同一时间只放行一次试探调用，其余仍视为熔断中
+            if breaker.probing {
+                return Err(self.open_err(Duration::ZERO));
+            }
+            breaker.probing = true;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+    fn record(&self, probe: bool, failed: bool) {
+        let mut breaker = self.breaker();
+        if probe {
+            breaker.probing = false;
+            breaker.window.clear();
+            if failed {
+                breaker.state = CircuitState::Open;
+                breaker.opened_at = Instant::now();
+            } else {
+                breaker.state = CircuitState::Closed;
+            }
+            return;
+        }
+        if breaker.state != CircuitState::Closed {
+            return;
+        }
+        breaker.window.push_back(failed);
+        while breaker.window.len() > self.window {
+            breaker.window.pop_front();
+        }
+        let calls = breaker.window.len();
+        let failures = breaker.window.iter().filter(|f| **f).count();
+        if calls >= self.min_calls && failures as f64 >= self.failure_ratio * calls as f64 {
+            breaker.state = CircuitState::Open;
+            breaker.opened_at = Instant::now();
+            breaker.window.clear();
+        }
+    }
+    fn breaker(&self) -> MutexGuard<'_, Breaker> {
+        self.breaker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn open_err(&self, remaining: Duration) -> TransError {
+        TransError::CircuitOpen(
+            ErrInfoBuildle::new()
+                .model("CircuitBreaker")
+                .method("trans()")
+                .original(format!("{} 熔断中", self.inner.provider()))
+                .data(format!("{}ms后重试", remaining.as_millis()))
+                .build(),
+        )
+    }
+}
+
+impl<'a> Translation<'a> for CircuitBreaker<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(CircuitBreaker {
+            inner: self.inner.build(),
+            breaker: self.breaker.clone(),
+            window: self.window,
+            min_calls: self.min_calls,
+            failure_ratio: self.failure_ratio,
+            cooldown: self.cooldown,
+        })
+    }
+
+    /// 只有`TransError::is_provider_failure`的错误计为失败
+    fn trans(&mut self) -> Result<String> {
+        let probe = self.admit()?;
+        let res = self.inner.trans();
+        match &res {
+            Ok(_) => self.record(probe, false),
+            Err(e) if e.is_provider_failure() => self.record(probe, true),
+            // 其他错误不说明接口的好坏，试探调用需重新进行
+            Err(_) if probe => self.breaker().probing = false,
+            Err(_) => {}
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Mock;
+    use std::sync::atomic::Ordering;
+
+    fn call(t: &mut CircuitBreaker<'_>, words: &str) -> Result<String> {
+        t.set_words(words);
+        t.trans()
+    }
+
+    #[test]
+    fn opens_after_failures_and_recovers() {
+        let mock = Mock::new();
+        let calls = mock.calls();
+        let mut t = CircuitBreaker::new(Box::new(mock))
+            .window(4)
+            .min_calls(2)
+            .failure_ratio(0.5)
+            .cooldown(Duration::from_millis(20));
+        assert!(call(&mut t, "ok").is_ok());
+        assert_eq!(t.state(), CircuitState::Closed);
+        assert!(call(&mut t, "fail").is_err());
+        assert_eq!(t.state(), CircuitState::Open);
+        // 熔断中不调用接口
+        assert!(matches!(
+            call(&mut t, "ok"),
+            Err(TransError::CircuitOpen(_))
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(t.state(), CircuitState::HalfOpen);
+        // 试探失败，重新熔断
+        assert!(matches!(
+            call(&mut t, "fail"),
+            Err(TransError::RequestError(_))
+        ));
+        assert_eq!(t.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(call(&mut t, "ok").unwrap(), "OK");
+        assert_eq!(t.state(), CircuitState::Closed);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn one_probe_at_a_time() {
+        let t = CircuitBreaker::new(Box::new(Mock::new())).cooldown(Duration::ZERO);
+        t.breaker().state = CircuitState::Open;
+        assert!(t.admit().unwrap());
+        assert!(matches!(t.admit(), Err(TransError::CircuitOpen(_))));
+        t.record(true, false);
+        assert_eq!(t.state(), CircuitState::Closed);
+        assert!(!t.admit().unwrap());
+    }
+
+    #[test]
+    fn window_and_min_calls() {
+        let mut t = CircuitBreaker::new(Box::new(Mock::new()))
+            .window(3)
+            .min_calls(3)
+            .failure_ratio(1.0);
+        for words in ["fail", "fail", "ok", "fail", "fail"] {
+            let _ = call(&mut t, words);
+            assert_eq!(t.state(), CircuitState::Closed);
+        }
+        // 窗口内3次全部失败
+        let _ = call(&mut t, "fail");
+        assert_eq!(t.state(), CircuitState::Open);
+        t.reset();
+        assert_eq!(t.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn failure_ratio_is_clamped() {
+        let ratio = |r| {
+            CircuitBreaker::new(Box::new(Mock::new()))
+                .failure_ratio(r)
+                .failure_ratio
+        };
+        assert_eq!(ratio(0.3), 0.3);
+        assert_eq!(ratio(2.0), 1.0);
+        assert_eq!(ratio(f64::NAN), 1.0);
+        assert_eq!(ratio(0.0), f64::MIN_POSITIVE);
+        assert_eq!(ratio(-1.0), f64::MIN_POSITIVE);
+
+        let mut t = CircuitBreaker::new(Box::new(Mock::new()))
+            .min_calls(2)
+            .failure_ratio(0.0);
+        let _ = call(&mut t, "ok");
+        assert_eq!(t.state(), CircuitState::Closed);
+        let _ = call(&mut t, "fail");
+        assert_eq!(t.state(), CircuitState::Open);
+    }
+}
@@ -0,0 +1,666 @@
+//! 翻译结果缓存
+//!
+//! 报告中大量重复的句子只需翻译一次。`CachedTranslator`可以包装任何翻译接口，
+//! 按(接口, 源语言, 目标语言, 领域, 规范化后的原文)缓存译文。
+//! 缓存存储实现`TransCache`，内置内存LRU缓存`MemoryCache`和SQLite文件缓存`SqliteCache`(需sqlite feature，默认开启)
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{capability::Capabilities, DomainType, Language, Result, Translation};
+
+/// 缓存键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub provider: String,
+    pub from: String,
+    pub to: String,
+    pub domain: String,
+    /// 规范化后的原文：去掉首尾空白，连续空白合并为一个空格
+    pub text: String,
+}
+
+impl CacheKey {
+    pub fn new(
+        provider: &str,
+        from: Language,
+        to: Language,
+        domain: DomainType,
+        text: &str,
+    ) -> Self {
+        CacheKey {
+            provider: provider.to_owned(),
+            from: Into::<&str>::into(from).to_owned(),
+            to: Into::<&str>::into(to).to_owned(),
+            domain: Into::<&str>::into(&domain).to_owned(),
+            text: normalize(text),
+        }
+    }
+    /// 翻译器当前设置的句子对应的缓存键
+    pub fn of<'a, T>(t: &T) -> Self
+    where
+        T: Translation<'a> + ?Sized,
+    {
+        CacheKey::new(t.provider(), t.from(), t.to(), t.domain(), t.get_words())
+    }
+}
+
+/// 去掉首尾空白，连续空白合并为一个空格
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 缓存存储。读写失败时`CachedTranslator`视为未命中，不影响翻译
+pub trait TransCache: Send {
+    fn get(&mut self, key: &CacheKey) -> Result<Option<String>>;
+    fn put(&mut self, key: CacheKey, value: String) -> Result<()>;
+    fn remove(&mut self, key: &CacheKey) -> Result<()>;
+    fn clear(&mut self) -> Result<()>;
+}
+
+/// 缓存命中统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Entry {
+    value: String,
+    inserted: Instant,
+    tick: u64,
+}
+
+/// 内存LRU缓存。超过容量时淘汰最久未使用的条目，设置了ttl时过期条目视为未命中
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    tick: u64,
+    entries: HashMap<CacheKey, Entry>,
+    /// 最近使用时间 => 键，用于找出最久未使用的条目
+    order: BTreeMap<u64, CacheKey>,
+}
+
+impl MemoryCache {
+    /// 最多缓存`capacity`条
+    pub fn new(capacity: usize) -> Self {
+        MemoryCache {
+            capacity,
+            ttl: None,
+            tick: 0,
+            entries: HashMap::default(),
+            order: BTreeMap::default(),
+        }
+    }
+    /// 条目的有效期
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+    pub fn get(&mut self, key: &CacheKey) -> Option<String> {
+        let expired = match self.entries.get(key) {
+            None => return None,
+            Some(entry) => self.ttl.is_some_and(|ttl| entry.inserted.elapsed() > ttl),
+        };
+        if expired {
+            self.remove(key);
+            return None;
+        }
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        entry.tick = self.tick;
+        self.order.insert(self.tick, key.clone());
+        Some(entry.value.clone())
+    }
+    pub fn put(&mut self, key: CacheKey, value: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        while self.entries.len() >= self.capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => {
+                    self.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted: Instant::now(),
+                tick: self.tick,
+            },
+        );
+    }
+    pub fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.tick);
+        }
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl TransCache for MemoryCache {
+    fn get(&mut self, key: &CacheKey) -> Result<Option<String>> {
+        Ok(MemoryCache::get(self, key))
+    }
+    fn put(&mut self, key: CacheKey, value: String) -> Result<()> {
+        MemoryCache::put(self, key, value);
+        Ok(())
+    }
+    fn remove(&mut self, key: &CacheKey) -> Result<()> {
+        MemoryCache::remove(self, key);
+        Ok(())
+    }
+    fn clear(&mut self) -> Result<()> {
+        MemoryCache::clear(self);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// 带缓存的翻译器。只缓存翻译成功的结果
+/// `build()`/`fork()`出的翻译器与原翻译器共用同一个缓存和统计
+pub struct CachedTranslator<'a, C = MemoryCache> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    cache: Arc<Mutex<C>>,
+    counter: Arc<Counter>,
+}
+
+impl<'a> CachedTranslator<'a, MemoryCache> {
+    /// 包装`inner`，使用内存缓存，最多缓存`capacity`条
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>, capacity: usize) -> Self {
+        Self::with_cache(inner, MemoryCache::new(capacity))
+    }
+    /// 缓存条目的有效期
+    pub fn ttl(self, ttl: Duration) -> Self {
+        self.cache().ttl = Some(ttl);
+        self
+    }
+}
+
+impl<'a, C: TransCache> CachedTranslator<'a, C> {
+    /// 使用指定的缓存存储，如`MemoryCache::new(10_000).ttl(..)`、`SqliteCache::open(..)`
+    pub fn with_cache(inner: Box<dyn Translation<'a> + 'a>, cache: C) -> Self {
+        CachedTranslator {
+            inner,
+            cache: Arc::new(Mutex::new(cache)),
+            counter: Arc::default(),
+        }
+    }
+    /// 命中统计
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counter.hits.load(Ordering::Relaxed),
+            misses: self.counter.misses.load(Ordering::Relaxed),
+        }
+    }
+    /// 清空缓存
+    pub fn clear(&self) -> Result<()> {
+        self.cache().clear()
+    }
+    /// 缓存存储，用于导入导出、清理等
+    pub fn cache(&self) -> std::sync::MutexGuard<'_, C> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<'a, C: TransCache + 'a> Translation<'a> for CachedTranslator<'a, C> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(CachedTranslator {
+            inner: self.inner.build(),
+            cache: self.cache.clone(),
+            counter: self.counter.clone(),
+        })
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        let key = CacheKey::of(self.inner.as_ref());
+        if let Ok(Some(hit)) = self.cache().get(&key) {
+            self.counter.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(hit);
+        }
+        self.counter.misses.fetch_add(1, Ordering::Relaxed);
+        let res = self.inner.trans()?;
+        let _ = self.cache().put(key, res.clone());
+        Ok(res)
+    }
+}
+
+/// `SqliteCache`导出/导入的一行(JSON lines)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheRecord {
+    pub provider: String,
+    pub from: String,
+    pub to: String,
+    pub domain: String,
+    pub source: String,
+    pub target: String,
+    /// 写入时间，Unix时间戳(秒)
+    pub created_at: u64,
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteCache;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader, BufWriter, Write},
+        path::Path,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use rusqlite::{params, Connection, OptionalExtension};
+
+    use super::{CacheKey, CacheRecord, TransCache};
+    use crate::{
+        err::{ErrInfoBuildle, TransError},
+        Result,
+    };
+
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS translations (
+        provider   TEXT NOT NULL,
+        src_lang   TEXT NOT NULL,
+        tgt_lang   TEXT NOT NULL,
+        domain     TEXT NOT NULL,
+        source     TEXT NOT NULL,
+        target     TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (provider, src_lang, tgt_lang, domain, source)
+    );
+    CREATE INDEX IF NOT EXISTS translations_created_at ON translations (created_at);";
+
+    /// SQLite文件缓存。重启后仍然有效，同一台机器上的多个任务可以共用一个文件
+    pub struct SqliteCache {
+        conn: Connection,
+    }
+
+    impl SqliteCache {
+        /// 打开或创建缓存文件
+        pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+            let conn = Connection::open(path).map_err(|e| sql_err("open()", e))?;
+            // WAL模式下多个进程可以同时读写，写锁冲突时最多等待5秒
+            conn.busy_timeout(Duration::from_secs(5))
+                .and_then(|_| conn.pragma_update(None, "journal_mode", "WAL"))
+                .and_then(|_| conn.execute_batch(SCHEMA))
+                .map_err(|e| sql_err("open()", e))?;
+            Ok(SqliteCache { conn })
+        }
+        /// 内存数据库，进程退出后丢失
+        pub fn open_in_memory() -> Result<Self> {
+            let conn = Connection::open_in_memory().map_err(|e| sql_err("open_in_memory()", e))?;
+            conn.execute_batch(SCHEMA)
+                .map_err(|e| sql_err("open_in_memory()", e))?;
+            Ok(SqliteCache { conn })
+        }
+        /// 条目数
+        pub fn len(&self) -> Result<usize> {
+            self.conn
+                .query_row("SELECT COUNT(*) FROM translations", [], |row| row.get(0))
+                .map_err(|e| sql_err("len()", e))
+        }
+        pub fn is_empty(&self) -> Result<bool> {
+            Ok(self.len()? == 0)
+        }
+        /// 删除写入时间早于`age`之前的条目，返回删除的条数
+        pub fn evict_older_than(&self, age: Duration) -> Result<usize> {
+            let before = now().saturating_sub(age.as_secs());
+            self.conn
+                .execute(
+                    "DELETE FROM translations WHERE created_at < ?1",
+                    params![before as i64],
+                )
+                .map_err(|e| sql_err("evict_older_than()", e))
+        }
+        /// 删除某个翻译接口的全部条目，返回删除的条数
+        pub fn evict_provider(&self, provider: &str) -> Result<usize> {
+            self.conn
+                .execute(
+                    "DELETE FROM translations WHERE provider = ?1",
+                    params![provider],
+                )
+                .map_err(|e| sql_err("evict_provider()", e))
+        }
+        /// 导出全部条目到JSON lines文件，返回导出的条数
+        pub fn export(&self, path: impl AsRef<Path>) -> Result<usize> {
+            let file = File::create(path).map_err(|e| io_err("export()", e))?;
+            let mut writer = BufWriter::new(file);
+            let mut stmt = self
+                .conn
+                .prepare(
+                    "SELECT provider, src_lang, tgt_lang, domain, source, target, created_at
+                     FROM translations ORDER BY created_at",
+                )
+                .map_err(|e| sql_err("export()", e))?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok(CacheRecord {
+                        provider: row.get(0)?,
+                        from: row.get(1)?,
+                        to: row.get(2)?,
+                        domain: row.get(3)?,
+                        source: row.get(4)?,
+                        target: row.get(5)?,
+                        created_at: row.get::<_, i64>(6)? as u64,
+                    })
+                })
+                .map_err(|e| sql_err("export()", e))?;
+            let mut count = 0;
+            for record in rows {
+                let record = record.map_err(|e| sql_err("export()", e))?;
+                let line = serde_json::to_string(&record).map_err(|e| io_err("export()", e))?;
+                writeln!(writer, "{}", line).map_err(|e| io_err("export()", e))?;
+                count += 1;
+            }
+            writer.flush().map_err(|e| io_err("export()", e))?;
+            Ok(count)
+        }
+        /// 从`export`导出的JSON lines文件导入，已存在的条目被覆盖，返回导入的条数
+        pub fn import(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+            let file = File::open(path).map_err(|e| io_err("import()", e))?;
+            let tx = self
+                .conn
+                .transaction()
+                .map_err(|e| sql_err("import()", e))?;
+            let mut count = 0;
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| io_err("import()", e))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let r: CacheRecord =
+                    serde_json::from_str(&line).map_err(|e| io_err("import()", e))?;
+                tx.execute(
+                    "INSERT OR REPLACE INTO translations VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        r.provider,
+                        r.from,
+                        r.to,
+                        r.domain,
+                        r.source,
+                        r.target,
+                        r.created_at as i64
+                    ],
+                )
+                .map_err(|e| sql_err("import()", e))?;
+                count += 1;
+            }
+            tx.commit().map_err(|e| sql_err("import()", e))?;
+            Ok(count)
+        }
+    }
+
+    impl TransCache for SqliteCache {
+        fn get(&mut self, key: &CacheKey) -> Result<Option<String>> {
+            self.conn
+                .query_row(
+                    "SELECT target FROM translations
+                     WHERE provider = ?1 AND src_lang = ?2 AND tgt_lang = ?3 AND domain = ?4 AND source = ?5",
+                    params![key.provider, key.from, key.to, key.domain, key.text],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| sql_err("get()", e))
+        }
+        fn put(&mut self, key: CacheKey, value: String) -> Result<()> {
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO translations VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        key.provider,
+                        key.from,
+                        key.to,
+                        key.domain,
+                        key.text,
+                        value,
+                        now() as i64
+                    ],
+                )
+                .map(|_| ())
+                .map_err(|e| sql_err("put()", e))
+        }
+        fn remove(&mut self, key: &CacheKey) -> Result<()> {
+            self.conn
+                .execute(
+                    "DELETE FROM translations
+                     WHERE provider = ?1 AND src_lang = ?2 AND tgt_lang = ?3 AND domain = ?4 AND source = ?5",
+                    params![key.provider, key.from, key.to, key.domain, key.text],
+                )
+                .map(|_| ())
+                .map_err(|e| sql_err("remove()", e))
+        }
+        fn clear(&mut self) -> Result<()> {
+            self.conn
+                .execute("DELETE FROM translations", [])
+                .map(|_| ())
+                .map_err(|e| sql_err("clear()", e))
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+
+    fn sql_err(method: &str, e: rusqlite::Error) -> TransError {
+        TransError::IOError(
+            ErrInfoBuildle::new()
+                .model("SqliteCache")
+                .method(method)
+                .original(e.to_string())
+                .build(),
+        )
+    }
+
+    fn io_err(method: &str, e: impl ToString) -> TransError {
+        TransError::IOError(
+            ErrInfoBuildle::new()
+                .model("SqliteCache")
+                .method(method)
+                .original(e.to_string())
+                .build(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Mock;
+
+    fn key(text: &str) -> CacheKey {
+        CacheKey::new(
+            "Mock",
+            Language::En,
+            Language::Zh,
+            DomainType::General,
+            text,
+        )
+    }
+
+    #[test]
+    fn normalize_whitespace() {
+        assert_eq!(normalize("  a \t b\n\nc  "), "a b c");
+        assert_eq!(key(" a  b"), key("a b"));
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut cache = MemoryCache::new(2);
+        cache.put(key("a"), "A".to_owned());
+        cache.put(key("b"), "B".to_owned());
+        // 读取a后，b成为最久未使用的
+        assert_eq!(cache.get(&key("a")).as_deref(), Some("A"));
+        cache.put(key("c"), "C".to_owned());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key("b")), None);
+        assert_eq!(cache.get(&key("a")).as_deref(), Some("A"));
+        assert_eq!(cache.get(&key("c")).as_deref(), Some("C"));
+        // 覆盖已有的键不淘汰其他条目
+        cache.put(key("a"), "A2".to_owned());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key("a")).as_deref(), Some("A2"));
+    }
+
+    #[test]
+    fn zero_capacity_and_ttl() {
+        let mut cache = MemoryCache::new(0);
+        cache.put(key("a"), "A".to_owned());
+        assert!(cache.is_empty());
+
+        let mut cache = MemoryCache::new(4).ttl(Duration::ZERO);
+        cache.put(key("a"), "A".to_owned());
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(cache.get(&key("a")), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn cached_translator_hits() {
+        let mock = Mock::new();
+        let calls = mock.calls();
+        let mut t = CachedTranslator::new(Box::new(mock), 16);
+        for words in ["hello", " hello ", "fail", "fail"] {
+            t.set_words(words);
+            let _ = t.trans();
+        }
+        // 失败的结果不缓存
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(t.stats(), CacheStats { hits: 1, misses: 3 });
+
+        // fork出的翻译器共用缓存
+        let mut forked = t.fork();
+        forked.set_words("hello");
+        assert_eq!(forked.trans().unwrap(), "HELLO");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(t.stats().hits, 2);
+
+        // 目标语言不同时不命中
+        t.set_to(Language::Jp).unwrap();
+        t.set_words("hello");
+        t.trans().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        t.clear().unwrap();
+        t.trans().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_export_import() {
+        let path = crate::testing::temp_path("cache-export.jsonl");
+        let mut cache = SqliteCache::open_in_memory().unwrap();
+        TransCache::put(&mut cache, key("a"), "A".to_owned()).unwrap();
+        TransCache::put(&mut cache, key("b"), "B".to_owned()).unwrap();
+        TransCache::remove(&mut cache, &key("b")).unwrap();
+        assert_eq!(cache.export(&path).unwrap(), 1);
+
+        let mut other = SqliteCache::open_in_memory().unwrap();
+        assert_eq!(other.import(&path).unwrap(), 1);
+        assert_eq!(
+            TransCache::get(&mut other, &key("a")).unwrap().as_deref(),
+            Some("A")
+        );
+        assert_eq!(TransCache::get(&mut other, &key("b")).unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+//! 接口能力
+//!
+//! 各接口支持的语言对、领域、最大长度、是否支持批量、需要的凭据。
+//! `Translation::set_from`等设置不支持的值时返回`TransError::Unsupported`，不再悄悄替换为其他语言
+use crate::{
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result,
+};
+
+/// 接口需要的凭据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    /// 无需凭据(CNKI使用内置秘钥)
+    Keyless,
+    /// 需要秘钥
+    SecretKey,
+    /// 需要appid和秘钥
+    AppIdAndSecretKey,
+}
+
+/// 接口能力，见`Translation::capabilities`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// 支持的(源语言, 目标语言)。源语言为`Language::Auto`表示支持自动检测该目标语言的源语言。
+    /// `None`为不限
+    pub pairs: Option<Vec<(Language, Language)>>,
+    /// 支持的领域，`None`为不限
+    pub domains: Option<Vec<DomainType>>,
+    /// 单次请求的最大长度
+    pub max_length: usize,
+    /// 一次请求能否翻译多段文本
+    pub batch: bool,
+    pub auth: Auth,
+}
+
+impl Capabilities {
+    /// 不限语言和领域。未声明能力的翻译器使用
+    pub fn unrestricted(max_length: usize) -> Self {
+        Capabilities {
+            pairs: None,
+            domains: None,
+            max_length,
+            batch: false,
+            auth: Auth::Keyless,
+        }
+    }
+    /// 能否以`language`为源语言
+    pub fn supports_from(&self, language: Language) -> bool {
+        self.pairs
+            .as_ref()
+            .is_none_or(|pairs| pairs.iter().any(|(from, _)| *from == language))
+    }
+    /// 能否以`language`为目标语言
+    pub fn supports_to(&self, language: Language) -> bool {
+        self.pairs
+            .as_ref()
+            .is_none_or(|pairs| pairs.iter().any(|(_, to)| *to == language))
+    }
+    pub fn supports_pair(&self, from: Language, to: Language) -> bool {
+        self.pairs
+            .as_ref()
+            .is_none_or(|pairs| pairs.contains(&(from, to)))
+    }
+    pub fn supports_domain(&self, domain: &DomainType) -> bool {
+        self.domains
+            .as_ref()
+            .is_none_or(|domains| domains.contains(domain))
+    }
+    /// 检查语言对和领域，不支持时返回`TransError::Unsupported`
+    pub fn check(
+        &self,
+        provider: &str,
+        from: Language,
+        to: Language,
+        domain: &DomainType,
+    ) -> Result<()> {
+        if !self.supports_pair(from, to) {
+            return Err(unsupported(
+                provider,
+                format!("不支持的语言对: {} => {}", from, to),
+            ));
+        }
+        if !self.supports_domain(domain) {
+            return Err(unsupported(provider, format!("不支持的领域: {}", domain)));
+        }
+        Ok(())
+    }
+    /// 合并两个接口的能力: 任一接口支持即支持，最大长度取较小值
+    pub fn union(self, other: Capabilities) -> Self {
+        fn merge<T: PartialEq>(a: Option<Vec<T>>, b: Option<Vec<T>>) -> Option<Vec<T>> {
+            let (mut a, b) = (a?, b?);
+            for v in b {
+                if !a.contains(&v) {
+                    a.push(v);
+                }
+            }
+            Some(a)
+        }
+        let auth = match (self.auth, other.auth) {
+            (Auth::AppIdAndSecretKey, _) | (_, Auth::AppIdAndSecretKey) => Auth::AppIdAndSecretKey,
+            (Auth::SecretKey, _) | (_, Auth::SecretKey) => Auth::SecretKey,
+            _ => Auth::Keyless,
+        };
+        Capabilities {
+            pairs: merge(self.pairs, other.pairs),
+            domains: merge(self.domains, other.domains),
+            max_length: self.max_length.min(other.max_length),
+            batch: self.batch && other.batch,
+            auth,
+        }
+    }
+}
+
+/// 接口不支持的设置
+pub(crate) fn unsupported(provider: &str, msg: String) -> TransError {
+    TransError::Unsupported(
+        ErrInfoBuildle::new()
+            .model(provider)
+            .method("capabilities()")
+            .original(msg)
+            .build(),
+    )
+}
@@ -0,0 +1,282 @@
+//! 命令行翻译工具
+//!
+//! ```text
+//! translate text "The lesion shows diffusion restriction."
+//! cat report.txt | translate --profile baidu --format bilingual stdin
+//! translate --to en --format jsonl file a.txt b.txt -o out/
+//! ```
+//! 接口和凭据由配置文件(`--config`或环境变量`TRANSLATION_CONFIG`)中的profile设置，见`config`模块。
+//! `--profile`也可以直接写接口名`cnki`、`baidu`，凭据由环境变量提供，如
+//! `TRANSLATION_BAIDU_APPID`、`TRANSLATION_BAIDU_SECRET_KEY`，也可以使用0.1的`BAIDU_APPID`、`BAIDU_SECRET_KEY`、`CNKI_SECRET_KEY`。
+//! 0.1的`--provider`仍可使用，同`--profile`。
+//! 命令行中的`--from`、`--to`、`--domain`优先于profile中的设置。
+//! 有句子翻译失败时，失败的位置输出`TRANS_ERR`，退出码为3。
+//!
+//! `translate`即调用`run`。注册自己的接口(见`registry`)后调用`run`，即可用`--profile`选择该接口:
+//! ```no_run
+//! # use translation_api::{cli, registry, Translation};
+//! # use translation_api::cnkis::CNKI;
+//! # fn in_house(_: Option<&str>) -> Box<dyn Translation<'_> + '_> { Box::new(CNKI::default_new()) }
+//! fn main() -> std::process::ExitCode {
+//!     registry::register("in-house", in_house).unwrap();
+//!     cli::run(std::env::args_os())
+//! }
+//! ```
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use crate::{
+    config::{self, Config, Profile},
+    document::{self, DocsOptions, Document, ErrorPolicy, Segment, SegmentOutcome},
+    err::TransError,
+    DomainType, Language, Translate, Translation, TRANS_ERR,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+#[derive(Parser)]
+#[command(name = "translate", version, about = "使用CNKI/Baidu翻译文本或文件")]
+struct Cli {
+    /// 配置文件中的profile或接口名(cnki baidu，或registry注册的名称)。默认为配置文件中的默认profile，没有时为cnki
+    #[arg(long, short, visible_alias = "provider")]
+    profile: Option<String>,
+    /// 源语言: auto或语言代码，如zh en ja zh-TW pt-BR
+    #[arg(long, short)]
+    from: Option<Language>,
+    /// 目标语言
+    #[arg(long, short)]
+    to: Option<Language>,
+    /// 领域: general electronics finance mechanics medicine novel，或custom:加接口的领域标识(如Baidu的custom:law)
+    #[arg(long, short)]
+    domain: Option<DomainType>,
+    /// 输出格式
+    #[arg(long, value_enum, default_value_t = Format::Plain)]
+    format: Format,
+    /// 并发翻译的线程数
+    #[arg(long, default_value_t = 1)]
+    workers: usize,
+    /// 每句失败后的重试次数
+    #[arg(long, default_value_t = 0)]
+    retries: usize,
+    /// 逐句检测源语言，用于不能自动检测语言的接口(如CNKI)
+    #[arg(long)]
+    detect: bool,
+    /// 检测到已是目标语言的句子原样输出，不调用翻译接口
+    #[arg(long)]
+    passthrough: bool,
+    /// 配置文件(TOML)
+    #[arg(long, env = config::CONFIG_ENV)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 翻译命令行参数，多个参数以空格连接
+    Text {
+        #[arg(required = true)]
+        text: Vec<String>,
+    },
+    /// 翻译标准输入
+    Stdin,
+    /// 翻译文件
+    File {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// 输出位置。一个输入文件时为输出文件，多个时为输出目录；不指定时输出到标准输出
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// 保留原文排版的译文
+    Plain,
+    /// 原文、译文逐句对照
+    Bilingual,
+    /// 每句一行JSON
+    Jsonl,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    source: &'a str,
+    translation: Option<&'a str>,
+    error: Option<String>,
+    provider: &'a str,
+}
+
+/// 按命令行参数运行，`args`的第一项为程序名
+pub fn run<I, T>(args: I) -> ExitCode
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let cli = Cli::parse_from(args);
+    match execute(&cli) {
+        Ok(true) => ExitCode::SUCCESS,
+        // 有句子翻译失败
+        Ok(false) => ExitCode::from(3),
+        Err(e) => {
+            eprintln!("translate: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn execute(cli: &Cli) -> Result<bool, String> {
+    // 先读入全部文本，翻译器借用的文本需比翻译器活得长
+    let inputs: Vec<(Option<&Path>, String)> = match &cli.command {
+        Command::Text { text } => vec![(None, text.join(" "))],
+        Command::Stdin => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("stdin: {}", e))?;
+            vec![(None, text)]
+        }
+        Command::File { paths, .. } => paths
+            .iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .map(|text| (Some(path.as_path()), text))
+                    .map_err(|e| format!("{}: {}", path.display(), e))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let output = match &cli.command {
+        Command::File { output, .. } => output.as_deref(),
+        _ => None,
+    };
+
+    let profile = profile(cli).map_err(|e| e.to_string().trim().to_owned())?;
+    let mut t = Translate::from_config(&profile).map_err(|e| e.to_string().trim().to_owned())?;
+    let options = DocsOptions {
+        retries: cli.retries,
+        workers: cli.workers,
+        detect_language: cli.detect,
+        passthrough_target: cli.passthrough,
+        ..Default::default()
+    };
+
+    let mut all_ok = true;
+    for (path, text) in &inputs {
+        let (out, ok) = translate(t.as_mut(), text, &options, cli.format);
+        all_ok &= ok;
+        match (path, output) {
+            (Some(path), Some(output)) => {
+                let target = if inputs.len() == 1 {
+                    output.to_path_buf()
+                } else {
+                    fs::create_dir_all(output)
+                        .map_err(|e| format!("{}: {}", output.display(), e))?;
+                    output.join(output_name(path, cli.format))
+                };
+                fs::write(&target, out).map_err(|e| format!("{}: {}", target.display(), e))?;
+            }
+            _ => {
+                if let (Some(path), true) = (path, inputs.len() > 1) {
+                    println!("==> {} <==", path.display());
+                }
+                print!("{}", out);
+            }
+        }
+    }
+    io::stdout().flush().map_err(|e| e.to_string())?;
+    Ok(all_ok)
+}
+
+/// 翻译一段文本，返回格式化后的输出和是否全部成功
+fn translate<'a>(
+    t: &mut (dyn Translation<'a> + 'a),
+    text: &'a str,
+    options: &DocsOptions,
+    format: Format,
+) -> (String, bool) {
+    let (skeleton, sources) = document::parse(text);
+    let outcomes = document::translate_segments(t, &sources, options);
+    let ok = outcomes.iter().all(|o| o.result.is_ok());
+    let out = match format {
+        Format::Plain => plain(skeleton, outcomes),
+        Format::Bilingual => outcomes
+            .iter()
+            .map(|o| format!("{}\n{}\n\n", o.source, translation(o)))
+            .collect(),
+        Format::Jsonl => outcomes
+            .iter()
+            .map(|o| {
+                let line = Line {
+                    source: o.source,
+                    translation: o.result.as_deref().ok(),
+                    error: o
+                        .result
+                        .as_ref()
+                        .err()
+                        .map(|e| e.to_string().trim().to_owned()),
+                    provider: o.provider,
+                };
+                serde_json::to_string(&line).unwrap_or_default() + "\n"
+            })
+            .collect(),
+    };
+    (out, ok)
+}
+
+fn plain<'a>(skeleton: Vec<document::Block<'a>>, outcomes: Vec<SegmentOutcome<'a>>) -> String {
+    let policy = ErrorPolicy::default();
+    let segments = outcomes
+        .into_iter()
+        .map(|o| Segment {
+            source: o.source,
+            translation: policy.resolve(o).ok().flatten().unwrap_or_default(),
+        })
+        .collect();
+    let mut text = Document { skeleton, segments }.text();
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+fn translation(outcome: &SegmentOutcome<'_>) -> String {
+    match &outcome.result {
+        Ok(r) => r.clone(),
+        Err(_) => TRANS_ERR.to_owned(),
+    }
+}
+
+fn output_name(path: &Path, format: Format) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match format {
+        Format::Jsonl => format!("{}.jsonl", stem),
+        _ => format!("{}.txt", stem),
+    }
+}
+
+/// 配置文件中的profile，命令行参数优先
+fn profile(cli: &Cli) -> Result<Profile, TransError> {
+    let config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let name = cli
+        .profile
+        .clone()
+        .or_else(|| config.default_name())
+        .unwrap_or_else(|| "cnki".to_owned());
+    let mut profile = config.profile(&name)?;
+    profile.from = cli.from.or(profile.from);
+    profile.to = cli.to.or(profile.to);
+    profile.domain = cli.domain.clone().or(profile.domain);
+    Ok(profile)
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use crate::{
+    capability::{unsupported, Auth, Capabilities},
     err::{ErrInfoBuildle, TransError},
     DomainType, Language, Translation,
 };
@@ -16,8 +17,9 @@ use serde_json::Value;
 
 type Result<T> = std::result::Result<T, TransError>;
 type Aes128EcbEnc = ecb::Encryptor<aes::Aes128>;
-const TOKEN_URL: &str = "https://dict.cnki.net/fyzs-front-api/getToken";
-const TRANSLATION_API: &str = "https://dict.cnki.net/fyzs-front-api/translate/literaltranslation";
+const CNKI_API: &str = "https://dict.cnki.net/fyzs-front-api";
+const TOKEN_URL: &str = "/getToken";
+const TRANSLATION_API: &str = "/translate/literaltranslation";
 const MAX_LEN: usize = 1200;
 
 #[derive(Debug, Deserialize)]
@@ -26,6 +28,7 @@ pub struct CnkiRes {
     pub code: usize,
     pub data: Data,
 }
+#[allow(non_snake_case)]
 #[derive(Debug, Deserialize)]
 pub struct Data {
     pub words: String,
@@ -44,21 +47,22 @@ pub struct Data {
 }
 
 pub struct CNKI<'a> {
-    words: &'a str,
+    words: String,
     encode_words: String,
     max_len: usize,
     key: &'a str,
     agent: String, // padding:
     tras_type: usize,
+    endpoint: String,
 }
 
 impl<'a> Translation<'a> for CNKI<'a> {
-    fn set_words(&mut self, words: &'a str) {
-        self.words = words;
+    fn set_words(&mut self, words: &str) {
+        self.words = words.to_owned();
     }
 
-    fn get_words(&self) -> &'a str {
-        self.words
+    fn get_words(&self) -> &str {
+        &self.words
     }
 
     fn trans(&mut self) -> Result<String> {
@@ -68,20 +72,29 @@ impl<'a> Translation<'a> for CNKI<'a> {
         }
     }
 
-    fn set_from(&mut self, language: crate::Language) {
-        match language {
-            crate::Language::Zh => self.tras_type = 0,
-            crate::Language::En => self.tras_type = 1,
-            _ => self.tras_type = 1,
-        }
+    /// 只支持中英互译，设置源语言即确定了目标语言
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.tras_type = match language {
+            Language::Zh => 0,
+            Language::En => 1,
+            _ => return Err(unsupported("CNKI", format!("不支持的源语言: {}", language))),
+        };
+        Ok(())
     }
 
-    fn set_to(&mut self, language: crate::Language) {
-        match language {
-            crate::Language::Zh => self.tras_type = 1,
-            crate::Language::En => self.tras_type = 0,
-            _ => self.tras_type = 1,
-        }
+    /// 只支持中英互译，设置目标语言即确定了源语言
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.tras_type = match language {
+            Language::Zh => 1,
+            Language::En => 0,
+            _ => {
+                return Err(unsupported(
+                    "CNKI",
+                    format!("不支持的目标语言: {}", language),
+                ))
+            }
+        };
+        Ok(())
     }
     fn set_appid(&mut self, _appid: &'a str) {}
 
@@ -89,7 +102,13 @@ impl<'a> Translation<'a> for CNKI<'a> {
         self.key = secret_key;
     }
 
-    fn set_domain(&mut self, _domain: DomainType) {}
+    /// 不区分领域，只支持`DomainType::General`
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        match domain {
+            DomainType::General => Ok(()),
+            _ => Err(unsupported("CNKI", format!("不支持的领域: {}", domain))),
+        }
+    }
 
     fn from(&self) -> Language {
         match self.tras_type {
@@ -119,40 +138,72 @@ impl<'a> Translation<'a> for CNKI<'a> {
         DomainType::General
     }
 
+    fn provider(&self) -> &'static str {
+        "CNKI"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            pairs: Some(vec![
+                (Language::Zh, Language::En),
+                (Language::En, Language::Zh),
+            ]),
+            domains: Some(vec![DomainType::General]),
+            max_length: self.max_len,
+            batch: false,
+            auth: Auth::Keyless,
+        }
+    }
+
     fn build(&self) -> Box<dyn Translation<'a> + 'a> {
-        Box::new(CNKI::new())
+        Box::new(CNKI {
+            endpoint: self.endpoint.clone(),
+            ..CNKI::new()
+        })
     }
 
     fn set_max_length(&mut self, l: usize) {
         self.max_len = l;
     }
 
-    fn max_length(&mut self) -> usize {
+    fn max_length(&self) -> usize {
         self.max_len
     }
 }
 
+impl<'a> Default for CNKI<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> CNKI<'a> {
     pub fn new() -> Self {
         Self {
-            words: "",
+            words: String::new(),
             key: "",
             agent: rand_user_agent::UserAgent::random().to_string(),
             encode_words: String::default(),
             tras_type: 1,
             max_len: MAX_LEN,
+            endpoint: CNKI_API.to_owned(),
         }
     }
     pub fn default_new() -> Self {
         Self {
-            words: "",
+            words: String::new(),
             key: "4e87183cfd3a45fe",
             agent: rand_user_agent::UserAgent::random().to_string(),
             encode_words: String::default(),
             tras_type: 1,
             max_len: MAX_LEN,
+            endpoint: CNKI_API.to_owned(),
         }
     }
+    /// 接口地址前缀，默认为`https://dict.cnki.net/fyzs-front-api`
+    pub fn set_endpoint(&mut self, endpoint: &str) {
+        self.endpoint = endpoint.trim_end_matches('/').to_owned();
+    }
     fn get_res(&mut self, token: String) -> Result<String> {
         self.aes_ecb_pkcs7_words_no_url();
         let mut map = HashMap::new();
@@ -160,7 +211,7 @@ impl<'a> CNKI<'a> {
         map.insert("translateType", self.tras_type.to_string());
         let client = reqwest::blocking::Client::new();
         let request = client
-            .post(TRANSLATION_API)
+            .post(format!("{}{}", self.endpoint, TRANSLATION_API))
             .header("Token", token.to_string())
             .header(USER_AGENT, self.agent.as_str())
             .header(REFERER, "https://dict.cnki.net/index")
@@ -172,7 +223,7 @@ impl<'a> CNKI<'a> {
                     .model("CNKI")
                     .method("get_res(token)")
                     .original(e.to_string())
-                    .data(format!("{}", token))
+                    .data(token.to_string())
                     .build(),
             )
         })?;
@@ -193,15 +244,16 @@ impl<'a> CNKI<'a> {
         Ok(res.data.mResult)
     }
     fn get_token(&self) -> Result<String> {
-        let response = reqwest::blocking::get(TOKEN_URL).map_err(|e| {
-            TransError::RequestError(
-                ErrInfoBuildle::new()
-                    .model("CNKI")
-                    .method("get_token()")
-                    .original(e.to_string())
-                    .build(),
-            )
-        })?;
+        let response =
+            reqwest::blocking::get(format!("{}{}", self.endpoint, TOKEN_URL)).map_err(|e| {
+                TransError::RequestError(
+                    ErrInfoBuildle::new()
+                        .model("CNKI")
+                        .method("get_token()")
+                        .original(e.to_string())
+                        .build(),
+                )
+            })?;
         let status = response.status();
         let body = response.json::<HashMap<String, Value>>().map_err(|e| {
             TransError::CNKIError(
@@ -225,6 +277,7 @@ impl<'a> CNKI<'a> {
             )),
         }
     }
+    #[allow(dead_code)]
     fn aes_ecb_pkcs7_words(&mut self) {
         let res = Aes128EcbEnc::new(self.key.as_bytes().into())
             .encrypt_padded_vec_mut::<Pkcs7>(self.words.as_bytes());
@@ -243,7 +296,7 @@ impl<'a> CNKI<'a> {
                     .build(),
             ));
         }
-        return Ok(true);
+        Ok(true)
     }
     fn aes_ecb_pkcs7_words_no_url(&mut self) {
         let res = Aes128EcbEnc::new(self.key.as_bytes().into())
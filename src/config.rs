@@ -0,0 +1,409 @@
+//! 配置文件
+//!
+//! 在TOML文件中按名称配置多组接口(profile)，用`Translate::from_config`创建设置好的翻译器:
+//! ```toml
+//! default = "baidu-medical"
+//!
+//! [profiles.baidu-medical]
+//! provider = "baidu"
+//! appid = "${BAIDU_APPID}"
+//! secret_key = "${BAIDU_SECRET_KEY}"
+//! from = "en"
+//! to = "zh"
+//! domain = "medicine"
+//! max_len = 1500
+//! qps = 1.0
+//!
+//! [profiles.cnki]
+//! provider = "cnki"
+//! endpoint = "https://dict.cnki.net/fyzs-front-api"
+//! ```
+//! 字符串中的`${NAME}`替换为环境变量，`${NAME:-默认值}`在环境变量不存在时使用默认值。
+//! 环境变量`TRANSLATION_<PROFILE>_<KEY>`覆盖文件中的设置，如`TRANSLATION_BAIDU_MEDICAL_APPID`，
+//! profile名称转为大写、非字母数字替换为`_`。
+//! 名称为接口名(`cnki`、`baidu`或`registry::register`注册的名称)的profile即使文件中没有也可使用，只由环境变量设置。
+//! 仍未设置凭据时，CNKI、Baidu读取0.1使用的环境变量`BAIDU_APPID`、`BAIDU_SECRET_KEY`、`CNKI_SECRET_KEY`
+use std::{collections::BTreeMap, env, fs, path::Path, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::{
+    capability::Auth,
+    err::{ErrInfoBuildle, TransError},
+    limit::RateLimit,
+    DomainType, Language, Result, Translate,
+};
+
+/// 配置文件的路径
+pub const CONFIG_ENV: &str = "TRANSLATION_CONFIG";
+/// 默认使用的profile，优先于文件中的`default`
+pub const PROFILE_ENV: &str = "TRANSLATION_PROFILE";
+
+/// 配置文件
+/// 文件中的其他顶层设置(如`translation-server`的设置)会被忽略
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    default: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, RawProfile>,
+}
+
+/// 文件中的一组设置，取用时才替换环境变量
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProfile {
+    provider: Option<String>,
+    appid: Option<String>,
+    secret_key: Option<String>,
+    endpoint: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    domain: Option<String>,
+    max_len: Option<usize>,
+    qps: Option<f64>,
+}
+
+/// 一组接口设置，未设置的项使用翻译器的默认值
+/// 克隆后共用同一个频率限制
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub provider: Translate,
+    pub appid: Option<String>,
+    pub secret_key: Option<String>,
+    /// 接口地址，见`Baidu::set_endpoint`、`CNKI::set_endpoint`
+    pub endpoint: Option<String>,
+    pub from: Option<Language>,
+    pub to: Option<Language>,
+    pub domain: Option<DomainType>,
+    pub max_len: Option<usize>,
+    /// 由`qps`创建
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl Profile {
+    /// 只指定接口的设置
+    pub fn new(name: &str, provider: Translate) -> Self {
+        Profile {
+            name: name.to_owned(),
+            provider,
+            appid: None,
+            secret_key: None,
+            endpoint: None,
+            from: None,
+            to: None,
+            domain: None,
+            max_len: None,
+            rate_limit: None,
+        }
+    }
+    /// 凭据是否齐全，按接口的`Capabilities::auth`: Baidu需要appid和秘钥，CNKI有默认秘钥。
+    /// 接口未注册时为`false`
+    pub fn has_credentials(&self) -> bool {
+        self.provider
+            .try_create()
+            .is_ok_and(|t| match t.capabilities().auth {
+                Auth::AppIdAndSecretKey => self.appid.is_some() && self.secret_key.is_some(),
+                Auth::SecretKey => self.secret_key.is_some(),
+                Auth::Keyless => true,
+            })
+    }
+}
+
+impl FromStr for Config {
+    type Err = TransError;
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| config_err("from_str()", e.to_string(), None))
+    }
+}
+
+impl Config {
+    /// 读取配置文件
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| config_err("load()", e.to_string(), Some(path.display().to_string())))?;
+        text.parse().map_err(|e: TransError| {
+            config_err(
+                "load()",
+                e.info().get("ORIGINAL").unwrap_or_default().to_owned(),
+                Some(path.display().to_string()),
+            )
+        })
+    }
+    /// 读取环境变量`TRANSLATION_CONFIG`指定的文件，未设置时为空配置
+    pub fn from_env() -> Result<Self> {
+        match env::var_os(CONFIG_ENV) {
+            Some(path) => Self::load(path),
+            None => Ok(Self::default()),
+        }
+    }
+    /// 文件中的profile名称
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.keys().map(String::as_str).collect()
+    }
+    /// 默认profile的名称: 环境变量`TRANSLATION_PROFILE`、文件中的`default`，文件中只有一个profile时为该profile
+    pub fn default_name(&self) -> Option<String> {
+        env::var(PROFILE_ENV)
+            .ok()
+            .or_else(|| self.default.clone())
+            .or_else(|| match self.profiles.len() {
+                1 => self.profiles.keys().next().cloned(),
+                _ => None,
+            })
+    }
+    /// 默认profile，见`default_name`
+    pub fn default_profile(&self) -> Result<Profile> {
+        let name = self.default_name().ok_or_else(|| {
+            config_err("default_profile()", "未指定默认的profile".to_owned(), None)
+        })?;
+        self.profile(&name)
+    }
+    /// 取出一组设置: 替换`${NAME}`，再用`TRANSLATION_<PROFILE>_<KEY>`环境变量覆盖，
+    /// 仍未设置的凭据读取`BAIDU_APPID`等0.1的环境变量
+    pub fn profile(&self, name: &str) -> Result<Profile> {
+        let err = |msg: String| config_err("profile()", msg, Some(name.to_owned()));
+        let mut raw = match self.profiles.get(name) {
+            Some(raw) => raw.clone(),
+            // 以接口名命名的profile可以不写在文件中
+            None if name.parse::<Translate>().is_ok() => RawProfile {
+                provider: Some(name.to_owned()),
+                ..Default::default()
+            },
+            None => return Err(err("没有该profile".to_owned())),
+        };
+        for field in [
+            &mut raw.provider,
+            &mut raw.appid,
+            &mut raw.secret_key,
+            &mut raw.endpoint,
+            &mut raw.from,
+            &mut raw.to,
+            &mut raw.domain,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *field = interpolate(field).map_err(err)?;
+        }
+        let prefix = env_prefix(name);
+        let var = |key: &str| env::var(format!("{}_{}", prefix, key)).ok();
+        let overrides = [
+            ("PROVIDER", &mut raw.provider),
+            ("APPID", &mut raw.appid),
+            ("SECRET_KEY", &mut raw.secret_key),
+            ("ENDPOINT", &mut raw.endpoint),
+            ("FROM", &mut raw.from),
+            ("TO", &mut raw.to),
+            ("DOMAIN", &mut raw.domain),
+        ];
+        for (key, field) in overrides {
+            if let Some(value) = var(key) {
+                *field = Some(value);
+            }
+        }
+        if let Some(value) = var("MAX_LEN") {
+            raw.max_len = Some(
+                value
+                    .parse()
+                    .map_err(|_| err(format!("MAX_LEN: {}", value)))?,
+            );
+        }
+        if let Some(value) = var("QPS") {
+            raw.qps = Some(value.parse().map_err(|_| err(format!("QPS: {}", value)))?);
+        }
+
+        let provider = raw
+            .provider
+            .as_deref()
+            .ok_or_else(|| err("未设置provider".to_owned()))?
+            .parse()?;
+        let rate_limit = match raw.qps {
+            Some(qps) => {
+                Some(RateLimit::per_second(qps).map_err(|_| err(format!("qps: {}", qps)))?)
+            }
+            None => None,
+        };
+        let legacy = |key: &str| {
+            let prefix = match provider {
+                Translate::Cnki => "CNKI",
+                Translate::Baidu => "BAIDU",
+                Translate::Registered(_) => return None,
+            };
+            env::var(format!("{}_{}", prefix, key)).ok()
+        };
+        Ok(Profile {
+            name: name.to_owned(),
+            provider,
+            appid: raw.appid.or_else(|| legacy("APPID")),
+            secret_key: raw.secret_key.or_else(|| legacy("SECRET_KEY")),
+            endpoint: raw.endpoint,
+            from: raw.from.as_deref().map(str::parse).transpose()?,
+            to: raw.to.as_deref().map(str::parse).transpose()?,
+            domain: raw.domain.as_deref().map(str::parse).transpose()?,
+            max_len: raw.max_len,
+            rate_limit,
+        })
+    }
+}
+
+/// 替换`${NAME}`和`${NAME:-默认值}`
+fn interpolate(value: &str) -> std::result::Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            return Err(format!("缺少`}}`: {}", value));
+        };
+        let expr = &rest[start + 2..start + len];
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+        match (env::var(name), default) {
+            (Ok(v), _) => out.push_str(&v),
+            (Err(_), Some(default)) => out.push_str(default),
+            (Err(_), None) => return Err(format!("环境变量{}不存在", name)),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `baidu-medical` => `TRANSLATION_BAIDU_MEDICAL`
+fn env_prefix(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+    format!("TRANSLATION_{}", name)
+}
+
+fn config_err(method: &str, err: String, data: Option<String>) -> TransError {
+    let mut info = ErrInfoBuildle::new();
+    info.model("Config").method(method).original(err);
+    if let Some(data) = data {
+        info.data(data);
+    }
+    TransError::ConfigError(info.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+default = "baidu-medical"
+
+[profiles.baidu-medical]
+provider = "baidu"
+appid = "${TRANSLATION_TEST_APPID}"
+secret_key = "${TRANSLATION_TEST_SECRET:-fallback}"
+from = "en"
+domain = "medicine"
+
+[profiles.cnki]
+provider = "cnki"
+endpoint = "https://example.com/${TRANSLATION_TEST_UNSET:-api}"
+"#;
+
+    #[test]
+    fn interpolate_env() {
+        env::set_var("TRANSLATION_TEST_HOST", "example.com");
+        assert_eq!(
+            interpolate("https://${TRANSLATION_TEST_HOST}/api").unwrap(),
+            "https://example.com/api"
+        );
+        assert_eq!(
+            interpolate("${TRANSLATION_TEST_HOST:-other}${TRANSLATION_TEST_NONE:-:8080}").unwrap(),
+            "example.com:8080"
+        );
+        assert_eq!(interpolate("plain").unwrap(), "plain");
+        assert!(interpolate("${TRANSLATION_TEST_NONE}").is_err());
+        assert!(interpolate("${TRANSLATION_TEST_HOST").is_err());
+    }
+
+    #[test]
+    fn profile_interpolates_and_overrides() {
+        let config: Config = FILE.parse().unwrap();
+        // 引用的环境变量不存在
+        assert!(matches!(
+            config.profile("baidu-medical"),
+            Err(TransError::ConfigError(_))
+        ));
+        env::set_var("TRANSLATION_TEST_APPID", "appid");
+        let profile = config.profile("baidu-medical").unwrap();
+        assert_eq!(profile.provider, Translate::Baidu);
+        assert_eq!(profile.appid.as_deref(), Some("appid"));
+        assert_eq!(profile.secret_key.as_deref(), Some("fallback"));
+        assert_eq!(profile.from, Some(Language::En));
+        assert_eq!(profile.domain, Some(DomainType::Medicine));
+        assert!(profile.has_credentials());
+
+        env::set_var("TRANSLATION_BAIDU_MEDICAL_SECRET_KEY", "secret");
+        env::set_var("TRANSLATION_BAIDU_MEDICAL_TO", "ja");
+        env::set_var("TRANSLATION_BAIDU_MEDICAL_MAX_LEN", "100");
+        let profile = config.profile("baidu-medical").unwrap();
+        assert_eq!(profile.secret_key.as_deref(), Some("secret"));
+        assert_eq!(profile.to, Some(Language::Jp));
+        assert_eq!(profile.max_len, Some(100));
+        env::set_var("TRANSLATION_BAIDU_MEDICAL_MAX_LEN", "many");
+        assert!(config.profile("baidu-medical").is_err());
+        env::remove_var("TRANSLATION_BAIDU_MEDICAL_MAX_LEN");
+
+        let cnki = config.profile("cnki").unwrap();
+        assert_eq!(cnki.endpoint.as_deref(), Some("https://example.com/api"));
+    }
+
+    #[test]
+    fn profile_names() {
+        let config: Config = FILE.parse().unwrap();
+        assert_eq!(config.profile_names(), ["baidu-medical", "cnki"]);
+        assert!(matches!(
+            config.profile("unknown"),
+            Err(TransError::ConfigError(_))
+        ));
+        // 以接口名命名的profile可以不写在文件中
+        let baidu = config.profile("Baidu").unwrap();
+        assert_eq!(baidu.provider, Translate::Baidu);
+        assert_eq!((baidu.from, baidu.to, baidu.domain), (None, None, None));
+        assert!(Config::default().profile("cnki").is_ok());
+        assert!(Config::default().profile("unknown").is_err());
+    }
+
+    #[test]
+    fn default_name() {
+        let config: Config = FILE.parse().unwrap();
+        assert_eq!(config.default_name().as_deref(), Some("baidu-medical"));
+        let single: Config = "[profiles.only]\nprovider = \"cnki\"".parse().unwrap();
+        assert_eq!(single.default_name().as_deref(), Some("only"));
+        assert_eq!(Config::default().default_name(), None);
+        assert!(Config::default().default_profile().is_err());
+        env::set_var(PROFILE_ENV, "cnki");
+        assert_eq!(config.default_name().as_deref(), Some("cnki"));
+        assert_eq!(Config::default().default_profile().unwrap().name, "cnki");
+        env::remove_var(PROFILE_ENV);
+    }
+
+    #[test]
+    fn legacy_credentials() {
+        env::set_var("CNKI_SECRET_KEY", "legacy");
+        let config: Config = "[profiles.file]\nprovider = \"cnki\"\nsecret_key = \"file\""
+            .parse()
+            .unwrap();
+        assert_eq!(
+            config.profile("cnki").unwrap().secret_key.as_deref(),
+            Some("legacy")
+        );
+        // 文件中的设置优先
+        assert_eq!(
+            config.profile("file").unwrap().secret_key.as_deref(),
+            Some("file")
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! 离线语言检测
+//!
+//! CNKI不能自动检测源语言，总按英文翻译。先按文字(汉字、假名、西里尔字母等)判断，
+//! 同一文字的多种语言再按字符三元组与内置的常用词样本比较。不访问网络，适合逐句检测
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::Language;
+
+/// 置信度不低于该值时，`DocsOptions::detect_language`才按检测结果设置源语言
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+/// 检测结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub language: Language,
+    /// 0~1，所有候选的置信度之和不超过1
+    pub confidence: f64,
+}
+
+/// 检测文本的语言，返回置信度最高的一种。没有可识别的文字时为`None`
+pub fn detect_language(text: &str) -> Option<Detection> {
+    detect_candidates(text).into_iter().next()
+}
+
+/// 检测文本的语言，返回所有候选，按置信度从高到低排列
+pub fn detect_candidates(text: &str) -> Vec<Detection> {
+    let mut counts: HashMap<Script, usize> = HashMap::new();
+    let (mut han, mut kana) = (0, 0);
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        let Some(s) = script(c) else {
+            continue;
+        };
+        match s {
+            Script::Han => han += 1,
+            Script::Kana => kana += 1,
+            _ => {}
+        }
+        // 日文混用汉字和假名，合并计算
+        let s = if s == Script::Kana { Script::Han } else { s };
+        *counts.entry(s).or_default() += 1;
+    }
+    let total: usize = counts.values().sum();
+    let mut res = Vec::new();
+    for (s, count) in counts {
+        let share = count as f64 / total as f64;
+        let candidates = match s {
+            Script::Han => chinese_or_japanese(text, han, kana),
+            Script::Latin | Script::Cyrillic | Script::Arabic => ngram(text, s),
+            _ => vec![(single(s), 1.0)],
+        };
+        res.extend(candidates.into_iter().map(|(language, p)| Detection {
+            language,
+            confidence: share * p,
+        }));
+    }
+    res.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    res
+}
+
+/// 文字
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Latin,
+    Han,
+    Kana,
+    Hangul,
+    Cyrillic,
+    Greek,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Tamil,
+    Telugu,
+    Sinhala,
+    Thai,
+    Lao,
+    Myanmar,
+    Khmer,
+    Georgian,
+    Armenian,
+    Ethiopic,
+}
+
+fn script(c: char) -> Option<Script> {
+    let s = match c as u32 {
+        0x41..=0x24F | 0x1E00..=0x1EFF => Script::Latin,
+        0x370..=0x3FF | 0x1F00..=0x1FFF => Script::Greek,
+        0x400..=0x52F => Script::Cyrillic,
+        0x530..=0x58F => Script::Armenian,
+        0x590..=0x5FF => Script::Hebrew,
+        0x600..=0x6FF | 0x750..=0x77F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Script::Arabic,
+        0x900..=0x97F => Script::Devanagari,
+        0x980..=0x9FF => Script::Bengali,
+        0xB80..=0xBFF => Script::Tamil,
+        0xC00..=0xC7F => Script::Telugu,
+        0xD80..=0xDFF => Script::Sinhala,
+        0xE00..=0xE7F => Script::Thai,
+        0xE80..=0xEFF => Script::Lao,
+        0x1000..=0x109F => Script::Myanmar,
+        0x10A0..=0x10FF => Script::Georgian,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+        0x1200..=0x137F => Script::Ethiopic,
+        0x1780..=0x17FF => Script::Khmer,
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9D => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FFFF => Script::Han,
+        _ => return None,
+    };
+    Some(s)
+}
+
+/// 只有一种语言使用的文字
+fn single(s: Script) -> Language {
+    match s {
+        Script::Hangul => Language::Kor,
+        Script::Greek => Language::El,
+        Script::Hebrew => Language::Heb,
+        Script::Devanagari => Language::Hi,
+        Script::Bengali => Language::Ben,
+        Script::Tamil => Language::Tam,
+        Script::Telugu => Language::Tel,
+        Script::Sinhala => Language::Sin,
+        Script::Thai => Language::Th,
+        Script::Lao => Language::Lao,
+        Script::Myanmar => Language::Bur,
+        Script::Khmer => Language::Hkm,
+        Script::Georgian => Language::Geo,
+        Script::Armenian => Language::Arm,
+        Script::Ethiopic => Language::Amh,
+        Script::Kana => Language::Jp,
+        Script::Latin | Script::Han | Script::Cyrillic | Script::Arabic => Language::Auto,
+    }
+}
+
+/// 简体字和对应的繁体字，按位置对应
+const SIMPLIFIED: &str = "这们来时说过发后会对为与国学经现动实进关点样还种问题长让体医疗检诊断华药边应开没么见东车门电话书爱气";
+const TRADITIONAL: &str = "這們來時說過發後會對為與國學經現動實進關點樣還種問題長讓體醫療檢診斷華藥邊應開沒麼見東車門電話書愛氣";
+
+/// 假名占汉字和假名的20%以上即为日文；其余按简繁特有字区分
+fn chinese_or_japanese(text: &str, han: usize, kana: usize) -> Vec<(Language, f64)> {
+    let jp = (kana as f64 / (han + kana) as f64 * 5.0).min(1.0);
+    let simplified = text.chars().filter(|c| SIMPLIFIED.contains(*c)).count();
+    let traditional = text.chars().filter(|c| TRADITIONAL.contains(*c)).count();
+    let cht = traditional as f64 / (traditional + simplified + 1) as f64;
+    vec![
+        (Language::Jp, jp),
+        (Language::Zh, (1.0 - jp) * (1.0 - cht)),
+        (Language::Cht, (1.0 - jp) * cht),
+    ]
+}
+
+/// 各语言的常用词，用于生成字符三元组
+const SAMPLES: &[(Language, &str)] = &[
+    (Language::En, "the of and to in is was for that with on as are by this be from or an at which were not have has it patient patients after disease treatment showed diagnosis clinical pain blood left right no history year old presented"),
+    (Language::Fra, "le la les de des du et en un une est dans pour que qui sur avec par au aux pas plus ce cette sont été patient patients maladie traitement après douleur sang gauche droite année ans diagnostic présenté était il elle"),
+    (Language::Spa, "el la los las de del y en un una es que por para con no se su al lo como más pero sus fue paciente pacientes enfermedad tratamiento después dolor sangre izquierda derecha años diagnóstico presentó"),
+    (Language::De, "der die das und in den von zu mit ist im nicht sich des auf für ein eine dem als auch es an werden aus bei nach wurde patient patienten krankheit behandlung schmerzen blut links rechts jahre diagnose zeigte"),
+    (Language::It, "il la di che e è un una per in non con del della dei le si sono da al alla come più anche paziente pazienti malattia trattamento dopo dolore sangue sinistra destra anni diagnosi presentato"),
+    (Language::Pt, "o a os as de do da dos das e em um uma é que não para com por se mais ao como foi são paciente pacientes doença tratamento após dor sangue esquerda direita anos diagnóstico apresentou"),
+    (Language::Nl, "de het een en van in is dat op te voor met zijn niet aan er die ook als bij door werd naar wordt patiënt patiënten ziekte behandeling pijn bloed links rechts jaar diagnose toonde"),
+    (Language::Pl, "i w na z że się nie do jest to o jak po co od przez dla jego był była są pacjent pacjenta choroba leczenie ból krew lewej prawej lat rozpoznanie wykazało"),
+    (Language::Cs, "a v na se je že s z do o k to jako by byl byla jsou pro pacient pacienta nemoc léčba bolest krev vlevo vpravo let diagnóza ukázala který které"),
+    (Language::Sk, "a v na sa je že s z do o k to ako by bol bola sú pre pacient pacienta choroba liečba bolesť krv vľavo vpravo rokov diagnóza ukázala ktorý ktoré"),
+    (Language::Swe, "och i att det som en på är av för med till den har de inte om ett var men från patient patienten sjukdom behandling smärta blod vänster höger år diagnos visade"),
+    (Language::Dan, "og i at det som en på er af for med til den har de ikke om et var men fra patient patienten sygdom behandling smerte blod venstre højre år diagnose viste"),
+    (Language::Nor, "og i at det som en på er av for med til den har de ikke om et var men fra pasient pasienten sykdom behandling smerte blod venstre høyre år diagnose viste"),
+    (Language::Fin, "ja on ei se että oli kun mutta hän myös tai joka ovat olla potilas potilaan sairaus hoito kipu veri vasen oikea vuotta diagnoosi osoitti jälkeen"),
+    (Language::Hu, "a az és hogy nem is egy van volt meg de csak már mint ez azt beteg betegség kezelés fájdalom vér bal jobb éves diagnózis mutatott után"),
+    (Language::Tr, "ve bir bu da de için ile çok olarak daha ne gibi sonra olan var hasta hastalık tedavi ağrı kan sol sağ yaşında tanı gösterdi değil"),
+    (Language::Rom, "și în de la cu a un o este că pe nu care pentru din au mai fost sunt pacient pacientul boală tratament durere sânge stânga dreapta ani diagnostic după"),
+    (Language::Vie, "và của là có không được trong cho một những người với này đã các bệnh nhân điều trị đau máu trái phải tuổi chẩn đoán sau khi"),
+    (Language::Id, "dan yang di ini itu dengan untuk tidak dari dalam akan pada juga ada adalah ke pasien penyakit pengobatan nyeri darah kiri kanan tahun diagnosis setelah menunjukkan"),
+    (Language::Hrv, "i je u na da se za od su ne s kao što to bio bila pacijent bolest liječenje bol krv lijevo desno godina dijagnoza nakon pokazao"),
+    (Language::Ru, "и в не на что с по как это он она они был была были к из для от при после пациент больной заболевание лечение боль кровь слева справа лет диагноз выявлено который"),
+    (Language::Ukr, "і в не на що з по як це він вона вони був була були до від для при після пацієнт хворий захворювання лікування біль кров зліва справа років діагноз виявлено який"),
+    (Language::Bul, "и в не на че с по как това той тя те беше бяха са от за при след пациент болен заболяване лечение болка кръв ляво дясно години диагноза който е"),
+    (Language::Srp, "и у на да се за од су не са као што је био била пацијент болест лечење бол крв лево десно година дијагноза након који њега"),
+    (Language::Kaz, "және бұл мен да де үшін бір деп оның болып еді жоқ науқас ауру емдеу ауырсыну қан сол оң жаста диагноз кейін көрсетті"),
+    (Language::Ara, "في من على أن إلى عن مع هذا هذه التي الذي كان لا ما هو هي المريض مرض العلاج ألم الدم اليسار اليمين سنة تشخيص بعد"),
+    (Language::Per, "و در به از که این را با است برای آن یک شد می بیمار بیماری درمان درد خون چپ راست سال تشخیص پس نشان داد"),
+    (Language::Urd, "اور میں کے کی کا سے کو ہے یہ پر نے تھا تھی ہیں مریض بیماری علاج درد خون بائیں دائیں سال تشخیص بعد"),
+];
+
+/// 一种语言的三元组对数概率
+struct Profile {
+    language: Language,
+    script: Script,
+    grams: HashMap<String, f64>,
+    /// 样本中没有的三元组
+    unseen: f64,
+}
+
+fn profiles() -> &'static [Profile] {
+    static PROFILES: OnceLock<Vec<Profile>> = OnceLock::new();
+    PROFILES.get_or_init(|| {
+        let counted: Vec<_> = SAMPLES
+            .iter()
+            .map(|(language, sample)| {
+                let s = sample.chars().find_map(script).unwrap_or(Script::Latin);
+                (*language, s, trigrams(sample, s))
+            })
+            .collect();
+        counted
+            .iter()
+            .map(|(language, s, grams)| {
+                // 同一文字所有样本中不同三元组的个数，用于加一平滑
+                let mut vocabulary: Vec<&String> = counted
+                    .iter()
+                    .filter(|(_, other, _)| other == s)
+                    .flat_map(|(_, _, g)| g.keys())
+                    .collect();
+                vocabulary.sort();
+                vocabulary.dedup();
+                let total = grams.values().sum::<usize>() + vocabulary.len();
+                Profile {
+                    language: *language,
+                    script: *s,
+                    grams: grams
+                        .iter()
+                        .map(|(g, c)| (g.clone(), ((c + 1) as f64 / total as f64).ln()))
+                        .collect(),
+                    unseen: (1.0 / total as f64).ln(),
+                }
+            })
+            .collect()
+    })
+}
+
+/// 取出属于`s`的单词，两端加空格后切分为三元组
+fn trigrams(text: &str, s: Script) -> HashMap<String, usize> {
+    let mut grams = HashMap::new();
+    let words = text
+        .split(|c: char| !c.is_alphabetic() || script(c) != Some(s))
+        .filter(|w| !w.is_empty());
+    for word in words {
+        let chars: Vec<char> = format!(" {} ", word.to_lowercase()).chars().collect();
+        for w in chars.windows(3) {
+            *grams.entry(w.iter().collect()).or_default() += 1;
+        }
+    }
+    grams
+}
+
+/// 按三元组比较同一文字的各语言样本。文本越短，置信度越低
+fn ngram(text: &str, s: Script) -> Vec<(Language, f64)> {
+    let grams = trigrams(text, s);
+    let n: usize = grams.values().sum();
+    let candidates: Vec<_> = profiles().iter().filter(|p| p.script == s).collect();
+    let scores: Vec<f64> = candidates
+        .iter()
+        .map(|p| {
+            let score: f64 = grams
+                .iter()
+                .map(|(g, c)| *c as f64 * p.grams.get(g).copied().unwrap_or(p.unseen))
+                .sum();
+            score / n.max(1) as f64
+        })
+        .collect();
+    let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let weight = n.min(40) as f64;
+    let exp: Vec<f64> = scores.iter().map(|s| ((s - best) * weight).exp()).collect();
+    let sum: f64 = exp.iter().sum();
+    candidates
+        .iter()
+        .zip(exp)
+        .map(|(p, e)| (p.language, e / sum))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(text: &str) -> Language {
+        detect_language(text).unwrap().language
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(detect("환자는 흉통을 호소했다."), Language::Kor);
+        assert_eq!(detect("Ο ασθενής παραπονέθηκε για πόνο."), Language::El);
+        assert_eq!(detect("ผู้ป่วยมีอาการปวดท้อง"), Language::Th);
+        assert_eq!(detect("रोगी को बुखार है।"), Language::Hi);
+        assert_eq!(detect_language("123 4.5 -- !?"), None);
+        assert!(detect_candidates("").is_empty());
+    }
+
+    #[test]
+    fn chinese_and_japanese() {
+        assert_eq!(detect("患者主诉胸痛，既往有高血压病史。"), Language::Zh);
+        assert_eq!(
+            detect("患者主訴胸痛，這是醫療檢查後的診斷。"),
+            Language::Cht
+        );
+        assert_eq!(detect("患者は発熱を訴えた。"), Language::Jp);
+    }
+
+    #[test]
+    fn trigram_languages() {
+        let cases = [
+            (
+                "The patient presented with chest pain and a history of diabetes.",
+                Language::En,
+            ),
+            (
+                "Le patient a présenté une douleur dans la poitrine après le traitement.",
+                Language::Fra,
+            ),
+            (
+                "El paciente presentó dolor después del tratamiento de la enfermedad.",
+                Language::Spa,
+            ),
+            (
+                "Der Patient wurde nach der Behandlung mit Schmerzen in die Klinik aufgenommen.",
+                Language::De,
+            ),
+            (
+                "Il paziente ha presentato dolore dopo il trattamento della malattia.",
+                Language::It,
+            ),
+            (
+                "Пациент жалуется на боль в груди после лечения.",
+                Language::Ru,
+            ),
+            ("Пацієнт скаржиться на біль після лікування.", Language::Ukr),
+            ("المريض يعاني من ألم في الصدر بعد العلاج.", Language::Ara),
+        ];
+        for (text, expected) in cases {
+            let d = detect_language(text).unwrap();
+            assert_eq!(d.language, expected, "{}", text);
+            assert!(d.confidence >= MIN_CONFIDENCE, "{} {}", text, d.confidence);
+        }
+    }
+
+    #[test]
+    fn confidence() {
+        let candidates = detect_candidates("Patient 患者主诉胸痛，既往有高血压病史。");
+        let sum: f64 = candidates.iter().map(|d| d.confidence).sum();
+        assert!(sum <= 1.0 + 1e-9);
+        assert!(candidates
+            .windows(2)
+            .all(|w| w[0].confidence >= w[1].confidence));
+        assert_eq!(candidates[0].language, Language::Zh);
+        // 单词越少，置信度越低
+        let short = detect_language("pain").unwrap().confidence;
+        let long = detect_language("The patient was admitted with severe pain in the left leg.")
+            .unwrap()
+            .confidence;
+        assert!(short < long);
+    }
+
+    #[test]
+    fn simplified_traditional_tables_match() {
+        assert_eq!(SIMPLIFIED.chars().count(), TRADITIONAL.chars().count());
+    }
+
+    #[test]
+    fn trigram_split() {
+        let grams = trigrams("Ab c, 中文", Script::Latin);
+        let mut keys: Vec<&str> = grams.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, [" ab", " c ", "ab "]);
+    }
+}
@@ -0,0 +1,786 @@
+//! 保留排版结构的文档翻译
+//!
+//! 将文档拆成骨架(段落、换行、缩进、列表符号、句间空白)和待翻译的句子，
+//! 翻译完成后按原骨架重建译文
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    detect::{self, MIN_CONFIDENCE},
+    err::TransError,
+    limit::RateLimit,
+    observer::{Event, Observer, Silent},
+    Language, Result, Translation, TRANS_ERR,
+};
+
+/// 句子切分使用的标点
+pub const PUNCTUATION: [char; 8] = ['!', '.', '?', ';', '？', '。', '；', '！'];
+
+/// 原样保留的句子，见`DocsOptions::passthrough_target`
+pub const PASSTHROUGH: &str = "Passthrough";
+
+/// 列表符号
+const BULLETS: [char; 7] = ['-', '*', '+', '•', '·', '▪', '●'];
+
+/// 文档骨架中的一块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block<'a> {
+    /// 原样保留的排版内容：换行、空行、缩进、列表符号、句间空白
+    Layout(&'a str),
+    /// 需要翻译的句子，值为`Document::segments`中的下标
+    Segment(usize),
+}
+
+/// 对齐的原文和译文
+#[derive(Debug, Clone)]
+pub struct Segment<'a> {
+    /// 原文句子(不含首尾空白)
+    pub source: &'a str,
+    /// 译文。翻译失败时按`ErrorPolicy`处理，默认为`TRANS_ERR`
+    pub translation: String,
+}
+
+/// 文档翻译结果
+#[derive(Debug, Clone)]
+pub struct Document<'a> {
+    /// 文档骨架
+    pub skeleton: Vec<Block<'a>>,
+    /// 与原文对齐的句子
+    pub segments: Vec<Segment<'a>>,
+}
+
+impl<'a> Document<'a> {
+    /// 按原文排版重建的译文
+    pub fn text(&self) -> String {
+        let mut res = String::new();
+        for block in &self.skeleton {
+            match block {
+                Block::Layout(s) => res.push_str(s),
+                Block::Segment(i) => res.push_str(&self.segments[*i].translation),
+            }
+        }
+        res
+    }
+    /// 按原文排版重建的原文，与输入相同
+    pub fn source(&self) -> String {
+        let mut res = String::new();
+        for block in &self.skeleton {
+            match block {
+                Block::Layout(s) => res.push_str(s),
+                Block::Segment(i) => res.push_str(self.segments[*i].source),
+            }
+        }
+        res
+    }
+}
+
+impl<'a> fmt::Display for Document<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// 句子翻译失败时的处理策略
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// 遇到第一个失败的句子即停止，返回该错误
+    FailFast,
+    /// 跳过失败的句子
+    Skip,
+    /// 失败的句子保留原文
+    KeepSource,
+    /// 失败的句子使用占位字符串
+    Placeholder(String),
+}
+
+impl Default for ErrorPolicy {
+    /// 与`docs()`一致，使用`TRANS_ERR`占位
+    fn default() -> Self {
+        ErrorPolicy::Placeholder(TRANS_ERR.to_owned())
+    }
+}
+
+impl ErrorPolicy {
+    /// 按策略处理单句结果。`Ok(None)`表示该句被跳过
+    pub fn resolve(&self, outcome: SegmentOutcome<'_>) -> Result<Option<String>> {
+        match (outcome.result, self) {
+            (Ok(r), _) => Ok(Some(r)),
+            (Err(e), ErrorPolicy::FailFast) => Err(e),
+            (Err(_), ErrorPolicy::Skip) => Ok(None),
+            (Err(_), ErrorPolicy::KeepSource) => Ok(Some(outcome.source.to_owned())),
+            (Err(_), ErrorPolicy::Placeholder(p)) => Ok(Some(p.to_owned())),
+        }
+    }
+}
+
+/// 文档翻译选项
+#[derive(Debug, Clone, Default)]
+pub struct DocsOptions {
+    /// 失败处理策略
+    pub policy: ErrorPolicy,
+    /// 单句失败后的重试次数，默认不重试
+    pub retries: usize,
+    /// 并发翻译的线程数。0或1为逐句顺序翻译。
+    /// 每个线程使用`Translation::fork()`得到的翻译器，结果仍按原文顺序返回
+    pub workers: usize,
+    /// 请求频率限制，并发时所有线程共用。重试也计入
+    pub rate_limit: Option<RateLimit>,
+    /// 逐句检测语言(见`detect::detect_language`)并设置为源语言，用于不能自动检测的接口(如CNKI)。
+    /// 置信度低于`detect::MIN_CONFIDENCE`、接口不支持该语言或与目标语言相同时，使用原设置
+    pub detect_language: bool,
+    /// 检测到句子已是目标语言时原样保留，不调用翻译接口，`SegmentOutcome::provider`为`PASSTHROUGH`。
+    /// 与`detect_language`同时开启时，中英混排等混合语言的文档逐句按语言分流
+    pub passthrough_target: bool,
+}
+
+/// 单句的翻译结果
+#[derive(Debug)]
+pub struct SegmentOutcome<'a> {
+    /// 原文句子
+    pub source: &'a str,
+    /// 译文或最后一次的错误
+    pub result: Result<String>,
+    /// 实际调用翻译接口的次数
+    pub attempts: usize,
+    /// 包括重试和频率限制等待在内的耗时
+    pub elapsed: Duration,
+    /// 最后一次调用实际使用的翻译接口，见`Translation::served_by`
+    pub provider: &'static str,
+}
+
+/// 按`docs()`的方式切分句子
+pub fn split(docs: &str) -> Vec<&str> {
+    docs.split_inclusive(&PUNCTUATION[..]).collect()
+}
+
+/// 翻译已切分好的句子(如`parse`返回的句子)，结果按原文顺序返回。其余同`Translation::docs_detailed`
+pub fn translate_segments<'a, T>(
+    t: &mut T,
+    sources: &[&'a str],
+    options: &DocsOptions,
+) -> Vec<SegmentOutcome<'a>>
+where
+    T: Translation<'a> + ?Sized,
+{
+    run(t, sources, options)
+}
+
+/// 翻译句子，结果按原文顺序返回。
+/// `ErrorPolicy::FailFast`时，遇到失败的句子即停止，最后一个结果为该错误
+pub(crate) fn run<'a, T>(
+    t: &mut T,
+    sources: &[&'a str],
+    options: &DocsOptions,
+) -> Vec<SegmentOutcome<'a>>
+where
+    T: Translation<'a> + ?Sized,
+{
+    run_observed(t, sources, options, &mut Silent)
+}
+
+/// 同`run`，翻译过程中的事件交给`observer`
+pub(crate) fn run_observed<'a, T>(
+    t: &mut T,
+    sources: &[&'a str],
+    options: &DocsOptions,
+    observer: &mut dyn Observer,
+) -> Vec<SegmentOutcome<'a>>
+where
+    T: Translation<'a> + ?Sized,
+{
+    run_cancellable(t, sources, options, observer, &AtomicBool::new(false))
+}
+
+/// 同`run_observed`。`cancel`为true后不再开始新的句子，已开始的句子照常完成，
+/// 返回已完成的结果(按原文顺序，不一定连续)
+pub(crate) fn run_cancellable<'a, T>(
+    t: &mut T,
+    sources: &[&'a str],
+    options: &DocsOptions,
+    observer: &mut dyn Observer,
+    cancel: &AtomicBool,
+) -> Vec<SegmentOutcome<'a>>
+where
+    T: Translation<'a> + ?Sized,
+{
+    let start = Instant::now();
+    let outcomes = if options.workers > 1 && sources.len() > 1 {
+        run_parallel(t, sources, options, observer, cancel)
+    } else {
+        run_sequential(t, sources, options, observer, cancel)
+    };
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    observer.on_event(&Event::Finished {
+        total: sources.len(),
+        translated: outcomes.len() - failed,
+        failed,
+        elapsed: start.elapsed(),
+    });
+    outcomes
+}
+
+fn run_sequential<'a, T>(
+    t: &mut T,
+    sources: &[&'a str],
+    options: &DocsOptions,
+    observer: &mut dyn Observer,
+    cancel: &AtomicBool,
+) -> Vec<SegmentOutcome<'a>>
+where
+    T: Translation<'a> + ?Sized,
+{
+    let mut outcomes = Vec::with_capacity(sources.len());
+    for (index, source) in sources.iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            break;
+        }
+        observer.on_event(&Event::SegmentStarted {
+            index,
+            total: sources.len(),
+            source,
+        });
+        let outcome = translate_segment(t, source, options, &mut |attempt, error| {
+            observer.on_event(&Event::Retrying {
+                index,
+                attempt,
+                error: &error,
+            })
+        });
+        notify_done(observer, index, &outcome);
+        let failed = outcome.result.is_err();
+        outcomes.push(outcome);
+        if failed && options.policy == ErrorPolicy::FailFast {
+            break;
+        }
+    }
+    outcomes
+}
+
+/// 工作线程发回调用线程的消息
+enum Message<'a> {
+    Started(usize),
+    Retrying(usize, usize, TransError),
+    Done(usize, SegmentOutcome<'a>),
+}
+
+/// 多线程翻译。每个线程持有一个`fork()`出的翻译器，按原文顺序领取句子
+fn run_parallel<'a, T>(
+    t: &mut T,
+    sources: &[&'a str],
+    options: &DocsOptions,
+    observer: &mut dyn Observer,
+    cancel: &AtomicBool,
+) -> Vec<SegmentOutcome<'a>>
+where
+    T: Translation<'a> + ?Sized,
+{
+    let fail_fast = options.policy == ErrorPolicy::FailFast;
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+    let mut slots: Vec<Option<SegmentOutcome<'a>>> = sources.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..options.workers.min(sources.len()) {
+            let mut worker = t.fork();
+            let tx = tx.clone();
+            let (next, stop) = (&next, &stop);
+            scope.spawn(move || {
+                while !stop.load(Ordering::SeqCst) && !cancel.load(Ordering::SeqCst) {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(source) = sources.get(i) else {
+                        break;
+                    };
+                    let _ = tx.send(Message::Started(i));
+                    let outcome =
+                        translate_segment(worker.as_mut(), source, options, &mut |attempt, e| {
+                            let _ = tx.send(Message::Retrying(i, attempt, e));
+                        });
+                    if fail_fast && outcome.result.is_err() {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    if tx.send(Message::Done(i, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+        for message in rx {
+            match message {
+                Message::Started(index) => observer.on_event(&Event::SegmentStarted {
+                    index,
+                    total: sources.len(),
+                    source: sources[index],
+                }),
+                Message::Retrying(index, attempt, error) => observer.on_event(&Event::Retrying {
+                    index,
+                    attempt,
+                    error: &error,
+                }),
+                Message::Done(index, outcome) => {
+                    notify_done(observer, index, &outcome);
+                    slots[index] = Some(outcome);
+                }
+            }
+        }
+    });
+
+    // 句子按顺序领取，FailFast停止时，第一个失败句子之前的结果都已完成
+    let mut outcomes = Vec::with_capacity(sources.len());
+    for outcome in slots.into_iter().flatten() {
+        let failed = outcome.result.is_err();
+        outcomes.push(outcome);
+        if failed && fail_fast {
+            break;
+        }
+    }
+    outcomes
+}
+
+fn notify_done(observer: &mut dyn Observer, index: usize, outcome: &SegmentOutcome<'_>) {
+    let event = match &outcome.result {
+        Ok(translation) => Event::SegmentTranslated {
+            index,
+            source: outcome.source,
+            translation,
+            attempts: outcome.attempts,
+            elapsed: outcome.elapsed,
+        },
+        Err(error) => Event::SegmentFailed {
+            index,
+            source: outcome.source,
+            error,
+            attempts: outcome.attempts,
+            elapsed: outcome.elapsed,
+        },
+    };
+    observer.on_event(&event);
+}
+
+/// 翻译单句，失败时重试。每次重试前调用`on_retry(第几次调用, 上一次的错误)`
+fn translate_segment<'a, T>(
+    t: &mut T,
+    source: &'a str,
+    options: &DocsOptions,
+    on_retry: &mut dyn FnMut(usize, TransError),
+) -> SegmentOutcome<'a>
+where
+    T: Translation<'a> + ?Sized,
+{
+    let start = Instant::now();
+    let detected = match options.detect_language || options.passthrough_target {
+        true => detect::detect_language(source)
+            .filter(|d| d.confidence >= MIN_CONFIDENCE)
+            .map(|d| d.language),
+        false => None,
+    };
+    if options.passthrough_target && detected == Some(t.to()) {
+        return SegmentOutcome {
+            source,
+            result: Ok(source.to_owned()),
+            attempts: 0,
+            elapsed: start.elapsed(),
+            provider: PASSTHROUGH,
+        };
+    }
+    let restore = match (options.detect_language, detected) {
+        (true, Some(detected)) => apply_detected(t, detected),
+        _ => None,
+    };
+    t.set_words(source);
+    let mut attempts = 0;
+    let outcome = loop {
+        if let Some(limit) = &options.rate_limit {
+            limit.acquire();
+        }
+        attempts += 1;
+        match t.trans() {
+            Err(e) if attempts <= options.retries => on_retry(attempts + 1, e),
+            result => {
+                break SegmentOutcome {
+                    source,
+                    result,
+                    attempts,
+                    elapsed: start.elapsed(),
+                    provider: t.served_by(),
+                }
+            }
+        }
+    };
+    if let Some(from) = restore {
+        let _ = t.set_from(from);
+    }
+    outcome
+}
+
+/// 按检测到的语言设置源语言，返回原来的源语言。未改变设置时为`None`
+fn apply_detected<'a, T>(t: &mut T, detected: Language) -> Option<Language>
+where
+    T: Translation<'a> + ?Sized,
+{
+    let from = t.from();
+    if detected == from || t.set_from(detected).is_err() {
+        return None;
+    }
+    // 只支持中英互译的接口(如CNKI)设置源语言即改变方向；其余接口源语言与目标语言相同时保留原设置
+    if t.from() == t.to() {
+        let _ = t.set_from(from);
+        return None;
+    }
+    Some(from)
+}
+
+/// 拆分文档，返回骨架和待翻译的句子。
+/// 骨架中`Block::Segment(i)`对应返回的第i个句子
+pub fn parse(docs: &str) -> (Vec<Block<'_>>, Vec<&str>) {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut offset = 0;
+    for line in docs.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let start = offset + body.len() - body.trim_start().len();
+        let start = start + bullet_len(&docs[start..offset + body.len()]);
+        let mut pos = start;
+        for sentence in docs[start..offset + body.len()].split_inclusive(&PUNCTUATION[..]) {
+            let trimmed = sentence.trim();
+            if !trimmed.is_empty() {
+                let s = pos + sentence.len() - sentence.trim_start().len();
+                ranges.push((s, s + trimmed.len()));
+            }
+            pos += sentence.len();
+        }
+        offset += line.len();
+    }
+
+    let mut skeleton = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut segments = Vec::with_capacity(ranges.len());
+    let mut last = 0;
+    for (i, (s, e)) in ranges.into_iter().enumerate() {
+        if s > last {
+            skeleton.push(Block::Layout(&docs[last..s]));
+        }
+        skeleton.push(Block::Segment(i));
+        segments.push(&docs[s..e]);
+        last = e;
+    }
+    if last < docs.len() {
+        skeleton.push(Block::Layout(&docs[last..]));
+    }
+    (skeleton, segments)
+}
+
+/// 行首列表符号(含其后的空白)的字节长度。`- `、`• `、`1. `、`2) `、`3、`等
+fn bullet_len(line: &str) -> usize {
+    let marker_end = match line.chars().next() {
+        Some(c) if BULLETS.contains(&c) => c.len_utf8(),
+        Some(c) if c.is_ascii_digit() => {
+            let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+            match line[digits..].chars().next() {
+                _ if digits > 3 => return 0,
+                // 中文序号后不一定有空白
+                Some('、') => {
+                    let end = digits + '、'.len_utf8();
+                    return end + whitespace_len(&line[end..]);
+                }
+                Some('.') | Some(')') => digits + 1,
+                _ => return 0,
+            }
+        }
+        _ => return 0,
+    };
+    match whitespace_len(&line[marker_end..]) {
+        0 => 0,
+        w => marker_end + w,
+    }
+}
+
+fn whitespace_len(s: &str) -> usize {
+    s.len() - s.trim_start().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Mock;
+
+    /// 按骨架重建原文
+    fn rebuild(skeleton: &[Block<'_>], sources: &[&str]) -> String {
+        skeleton
+            .iter()
+            .map(|b| match b {
+                Block::Layout(s) => *s,
+                Block::Segment(i) => sources[*i],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let docs = "Title line.\r\n\n  - First item. Second sentence!\n\t2) Numbered item?\n3、中文序号。第二句。\n\ntrailing space.   \n";
+        let (skeleton, sources) = parse(docs);
+        assert_eq!(rebuild(&skeleton, &sources), docs);
+        assert_eq!(
+            sources,
+            [
+                "Title line.",
+                "First item.",
+                "Second sentence!",
+                "Numbered item?",
+                "中文序号。",
+                "第二句。",
+                "trailing space.",
+            ]
+        );
+        assert_eq!(skeleton[0], Block::Segment(0));
+        assert_eq!(skeleton[1], Block::Layout("\r\n\n  - "));
+    }
+
+    #[test]
+    fn parse_empty_and_blank() {
+        assert_eq!(parse(""), (vec![], vec![]));
+        let (skeleton, sources) = parse("\n  \n");
+        assert!(sources.is_empty());
+        assert_eq!(skeleton, [Block::Layout("\n  \n")]);
+    }
+
+    #[test]
+    fn document_text_uses_layout() {
+        let docs = "- A.\n- B.";
+        let (skeleton, sources) = parse(docs);
+        let segments = sources
+            .iter()
+            .map(|s| Segment {
+                source: s,
+                translation: s.to_lowercase(),
+            })
+            .collect();
+        let document = Document { skeleton, segments };
+        assert_eq!(document.source(), docs);
+        assert_eq!(document.text(), "- a.\n- b.");
+    }
+
+    #[test]
+    fn bullets() {
+        assert_eq!(bullet_len("- item"), 2);
+        assert_eq!(bullet_len("•\titem"), '•'.len_utf8() + 1);
+        assert_eq!(bullet_len("12. item"), 4);
+        assert_eq!(bullet_len("3) item"), 3);
+        assert_eq!(bullet_len("3、条目"), 1 + '、'.len_utf8());
+        // 不是列表符号
+        assert_eq!(bullet_len("-5 mg"), 0);
+        assert_eq!(bullet_len("2024. year"), 0);
+        assert_eq!(bullet_len("3.5 mg"), 0);
+        assert_eq!(bullet_len("item"), 0);
+        assert_eq!(bullet_len(""), 0);
+    }
+
+    fn outcome(result: Result<String>) -> SegmentOutcome<'static> {
+        SegmentOutcome {
+            source: "source",
+            result,
+            attempts: 1,
+            elapsed: Duration::ZERO,
+            provider: "Mock",
+        }
+    }
+
+    fn failed() -> Result<String> {
+        Err(TransError::RequestError(
+            crate::err::ErrInfoBuildle::new().model("Mock").build(),
+        ))
+    }
+
+    #[test]
+    fn resolve_policies() {
+        for policy in [
+            ErrorPolicy::FailFast,
+            ErrorPolicy::Skip,
+            ErrorPolicy::KeepSource,
+            ErrorPolicy::default(),
+        ] {
+            let ok = policy.resolve(outcome(Ok("译文".to_owned()))).unwrap();
+            assert_eq!(ok.as_deref(), Some("译文"));
+        }
+        assert!(ErrorPolicy::FailFast.resolve(outcome(failed())).is_err());
+        assert_eq!(ErrorPolicy::Skip.resolve(outcome(failed())).unwrap(), None);
+        assert_eq!(
+            ErrorPolicy::KeepSource
+                .resolve(outcome(failed()))
+                .unwrap()
+                .as_deref(),
+            Some("source")
+        );
+        assert_eq!(
+            ErrorPolicy::default()
+                .resolve(outcome(failed()))
+                .unwrap()
+                .as_deref(),
+            Some(TRANS_ERR)
+        );
+        assert_eq!(
+            ErrorPolicy::Placeholder("[?]".to_owned())
+                .resolve(outcome(failed()))
+                .unwrap()
+                .as_deref(),
+            Some("[?]")
+        );
+    }
+
+    fn parallel(policy: ErrorPolicy) -> DocsOptions {
+        DocsOptions {
+            policy,
+            workers: 4,
+            ..DocsOptions::default()
+        }
+    }
+
+    #[test]
+    fn parallel_keeps_order() {
+        // 前面的句子更长、更慢，完成顺序与原文相反
+        let sources: Vec<String> = (0..8).rev().map(|n| "a".repeat(n * 3 + 1)).collect();
+        let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+        let mut t = Mock::new().with_delay(Duration::from_millis(1));
+        let calls = t.calls();
+        let outcomes = run(&mut t, &sources, &parallel(ErrorPolicy::default()));
+        assert_eq!(
+            calls.load(std::sync::atomic::Ordering::SeqCst),
+            sources.len()
+        );
+        let results: Vec<String> = outcomes.into_iter().map(|o| o.result.unwrap()).collect();
+        let expected: Vec<String> = sources.iter().map(|s| s.to_uppercase()).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let sources = ["one.", "fail two.", "three.", "four.", "fail five.", "six."];
+        let collect = |workers| {
+            let options = DocsOptions {
+                workers,
+                ..DocsOptions::default()
+            };
+            run(&mut Mock::new(), &sources, &options)
+                .into_iter()
+                .map(|o| (o.source, o.result.ok()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(collect(3), collect(0));
+    }
+
+    #[test]
+    fn parallel_fail_fast_stops_at_first_failure() {
+        let sources = ["a.", "b.", "fail c.", "d.", "e.", "f.", "g.", "h."];
+        let outcomes = run(&mut Mock::new(), &sources, &parallel(ErrorPolicy::FailFast));
+        let results: Vec<_> = outcomes
+            .iter()
+            .map(|o| (o.source, o.result.is_ok()))
+            .collect();
+        assert_eq!(results, [("a.", true), ("b.", true), ("fail c.", false)]);
+    }
+
+    #[test]
+    fn cancel_stops_dispatching() {
+        let sources = ["a.", "b.", "c.", "d.", "e.", "f."];
+        for workers in [0, 2] {
+            let mut t = Mock::new().with_delay(Duration::from_millis(5));
+            let calls = t.calls();
+            let cancel = AtomicBool::new(false);
+            let options = DocsOptions {
+                workers,
+                ..DocsOptions::default()
+            };
+            let outcomes = run_cancellable(
+                &mut t,
+                &sources,
+                &options,
+                &mut |event: &Event<'_>| {
+                    if let Event::SegmentTranslated { .. } = event {
+                        cancel.store(true, Ordering::SeqCst);
+                    }
+                },
+                &cancel,
+            );
+            // 取消前已开始的句子照常完成
+            assert!(outcomes.len() < sources.len(), "{}", workers);
+            assert!(outcomes.iter().all(|o| o.result.is_ok()));
+            assert_eq!(calls.load(Ordering::SeqCst), outcomes.len());
+        }
+    }
+
+    #[test]
+    fn passthrough_mixed_languages() {
+        let sources = [
+            "The patient presented with chest pain and a history of diabetes.",
+            "患者主诉胸痛，既往有高血压病史。",
+            "He was admitted to the hospital for treatment.",
+        ];
+        for workers in [0, 2] {
+            let mut t = Mock::new();
+            let calls = t.calls();
+            let options = DocsOptions {
+                workers,
+                detect_language: true,
+                passthrough_target: true,
+                ..DocsOptions::default()
+            };
+            let outcomes = run(&mut t, &sources, &options);
+            let routed: Vec<_> = outcomes
+                .iter()
+                .map(|o| (o.provider, o.attempts, o.result.as_deref().unwrap()))
+                .collect();
+            assert_eq!(
+                routed,
+                [
+                    ("Mock", 1, sources[0].to_uppercase().as_str()),
+                    (PASSTHROUGH, 0, sources[1]),
+                    ("Mock", 1, sources[2].to_uppercase().as_str()),
+                ],
+                "{}",
+                workers
+            );
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+            assert_eq!(t.from(), Language::En);
+        }
+    }
+
+    #[test]
+    fn passthrough_needs_confident_target() {
+        let sources = ["患者主诉胸痛。", "pain"];
+        let mut t = Mock::new();
+        let options = DocsOptions {
+            passthrough_target: true,
+            ..DocsOptions::default()
+        };
+        let providers: Vec<_> = run(&mut t, &sources, &options)
+            .into_iter()
+            .map(|o| o.provider)
+            .collect();
+        assert_eq!(providers, [PASSTHROUGH, "Mock"]);
+        // 目标语言为英文时中文句子照常翻译
+        let _ = t.set_to(Language::En);
+        let providers: Vec<_> = run(&mut t, &sources[..1], &options)
+            .into_iter()
+            .map(|o| o.provider)
+            .collect();
+        assert_eq!(providers, ["Mock"]);
+    }
+
+    #[test]
+    fn detected_language_sets_source() {
+        let mut t = Mock::new();
+        assert_eq!(apply_detected(&mut t, Language::Fra), Some(Language::En));
+        assert_eq!(t.from(), Language::Fra);
+        let _ = t.set_from(Language::En);
+        // 与源语言相同或与目标语言相同时不改变设置
+        assert_eq!(apply_detected(&mut t, Language::En), None);
+        assert_eq!(apply_detected(&mut t, Language::Zh), None);
+        assert_eq!(t.from(), Language::En);
+    }
+}
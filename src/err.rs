@@ -1,23 +1,29 @@
 use std::{collections::HashMap, fmt};
 
+#[derive(Default)]
 pub struct ErrInfoBuildle(HashMap<String, String>);
 impl ErrInfoBuildle {
     pub fn new() -> ErrInfoBuildle {
         ErrInfoBuildle(HashMap::default())
     }
 
-    pub fn model<'a>(&mut self, model: &'a str) -> &mut Self {
+    pub fn model(&mut self, model: &str) -> &mut Self {
         self.0.insert("MODEL".to_owned(), model.to_owned());
         self
     }
-    pub fn method<'a>(&mut self, method: &'a str) -> &mut Self {
+    pub fn method(&mut self, method: &str) -> &mut Self {
         self.0.insert("METHOD".to_owned(), method.to_owned());
         self
     }
-    pub fn data<'a>(&mut self, data: String) -> &mut Self {
+    pub fn data(&mut self, data: String) -> &mut Self {
         self.0.insert("DATA".to_owned(), data);
         self
     }
+    /// 接口返回的错误码，如Baidu的`54004`
+    pub fn code(&mut self, code: &str) -> &mut Self {
+        self.0.insert("CODE".to_owned(), code.to_owned());
+        self
+    }
     pub fn original(&mut self, err: String) -> &mut Self {
         self.0.insert("ORIGINAL".to_owned(), err);
         self
@@ -34,50 +40,122 @@ impl ErrInfoBuildle {
     }
 }
 
+#[derive(Debug)]
 pub struct ErrInfo {
     // Original: String,
-    Info: HashMap<String, String>,
+    info: HashMap<String, String>,
 }
 
 impl ErrInfo {
     pub fn build(info: HashMap<String, String>) -> Self {
         Self {
             // Original: original,
-            Info: info,
+            info,
         }
     }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.info.get(key).map(String::as_str)
+    }
     pub fn add<'a>(&mut self, key: &'a str, value: &'a str) -> &mut Self {
-        self.Info.insert(key.to_string(), value.to_string());
+        self.info.insert(key.to_string(), value.to_string());
         self
     }
 }
 impl fmt::Display for ErrInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // let a = write!(f, "");
-        for info in &self.Info {
+        for info in &self.info {
             write!(f, "\n{}=>{}", info.0, info.1)?;
         }
         Ok(())
     }
 }
 
+#[derive(Debug)]
 pub enum TransError {
     RequestError(ErrInfo),
     CNKIError(ErrInfo),
     BaiduError(ErrInfo),
+    /// 读写本地文件(检查点等)出错
+    IOError(ErrInfo),
+    /// 熔断中，未调用接口
+    CircuitOpen(ErrInfo),
+    /// 本月用量已达上限，未调用接口
+    BudgetExceeded(ErrInfo),
+    /// 配置错误: 配置文件、不支持的接口/语言/领域等
+    ConfigError(ErrInfo),
+    /// 接口不支持的语言、语言对或领域，未调用接口
+    Unsupported(ErrInfo),
+}
+impl TransError {
+    pub fn info(&self) -> &ErrInfo {
+        match self {
+            TransError::RequestError(e)
+            | TransError::CNKIError(e)
+            | TransError::BaiduError(e)
+            | TransError::IOError(e)
+            | TransError::CircuitOpen(e)
+            | TransError::BudgetExceeded(e)
+            | TransError::ConfigError(e)
+            | TransError::Unsupported(e) => e,
+        }
+    }
+    /// 接口返回的错误码
+    pub fn code(&self) -> Option<&str> {
+        self.info().get("CODE")
+    }
+    /// 是否为调用方的错误(不支持的设置、配置错误、Baidu本地的长度和参数检查)，
+    /// 未调用接口，重试不会成功
+    pub fn is_client_error(&self) -> bool {
+        match self {
+            TransError::Unsupported(_) | TransError::ConfigError(_) => true,
+            TransError::BaiduError(e) => e.get("METHOD") == Some(crate::baidu::CHECK),
+            _ => false,
+        }
+    }
+    /// 是否为翻译接口本身的失败(请求失败、接口返回错误等)，换一个接口可能成功
+    pub fn is_provider_failure(&self) -> bool {
+        match self {
+            TransError::RequestError(_)
+            | TransError::CNKIError(_)
+            | TransError::BaiduError(_)
+            | TransError::CircuitOpen(_)
+            | TransError::BudgetExceeded(_) => true,
+            TransError::IOError(_) | TransError::ConfigError(_) | TransError::Unsupported(_) => {
+                false
+            }
+        }
+    }
 }
 impl fmt::Display for TransError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TransError::RequestError(e) => {
-                write!(f, "【TRANSLATION】 RequestError : {}\n", e.to_string())
+                writeln!(f, "【TRANSLATION】 RequestError : {}", e)
             }
             TransError::CNKIError(e) => {
-                write!(f, "【TRANSLATION】 CNKIError : {}\n", e.to_string())
+                writeln!(f, "【TRANSLATION】 CNKIError : {}", e)
             }
             TransError::BaiduError(e) => {
-                write!(f, "【TRANSLATION】 BaiduError : {}\n", e.to_string())
+                writeln!(f, "【TRANSLATION】 BaiduError : {}", e)
+            }
+            TransError::IOError(e) => {
+                writeln!(f, "【TRANSLATION】 IOError : {}", e)
+            }
+            TransError::CircuitOpen(e) => {
+                writeln!(f, "【TRANSLATION】 CircuitOpen : {}", e)
+            }
+            TransError::BudgetExceeded(e) => {
+                writeln!(f, "【TRANSLATION】 BudgetExceeded : {}", e)
+            }
+            TransError::ConfigError(e) => {
+                writeln!(f, "【TRANSLATION】 ConfigError : {}", e)
+            }
+            TransError::Unsupported(e) => {
+                writeln!(f, "【TRANSLATION】 Unsupported : {}", e)
             }
         }
     }
 }
+
+impl std::error::Error for TransError {}
@@ -0,0 +1,325 @@
+//! 多个翻译接口依次尝试
+//!
+//! CNKI接口不稳定，Baidu需要付费额度。按顺序组合多个接口，前一个接口失败时自动换下一个
+use crate::{
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 按顺序尝试多个接口的翻译器
+///
+/// 只有`TransError::is_provider_failure`的错误才会换下一个接口，其余错误直接返回。
+/// 语言、领域、句子、最大长度会设置到所有接口；各接口的appid、秘钥不同，需在加入前分别设置，
+/// `set_appid`/`set_secret_key`不起作用。
+/// 有一个接口支持即可设置语言、领域，翻译时跳过不支持当前语言对或领域的接口
+pub struct FallbackTranslator<'a> {
+    providers: Vec<Box<dyn Translation<'a> + 'a>>,
+    from: Language,
+    to: Language,
+    domain: DomainType,
+    last_provider: Option<&'static str>,
+    failures: Vec<(&'static str, TransError)>,
+}
+
+impl<'a> FallbackTranslator<'a> {
+    /// `providers`按尝试顺序排列
+    /// 语言、领域取自第一个接口
+    pub fn new(providers: Vec<Box<dyn Translation<'a> + 'a>>) -> Self {
+        let first = providers.first();
+        FallbackTranslator {
+            from: first.map_or(Language::Auto, |p| p.from()),
+            to: first.map_or(Language::Zh, |p| p.to()),
+            domain: first.map_or(DomainType::General, |p| p.domain()),
+            providers,
+            last_provider: None,
+            failures: Vec::new(),
+        }
+    }
+    /// 在末尾追加一个接口
+    pub fn then(mut self, provider: Box<dyn Translation<'a> + 'a>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+    pub fn providers(&self) -> &[Box<dyn Translation<'a> + 'a>] {
+        &self.providers
+    }
+    /// 上一次`trans()`成功时产生译文的接口
+    pub fn last_provider(&self) -> Option<&'static str> {
+        self.last_provider
+    }
+    /// 上一次`trans()`中失败的接口及其错误，按尝试顺序排列
+    pub fn failures(&self) -> &[(&'static str, TransError)] {
+        &self.failures
+    }
+}
+
+impl<'a> Translation<'a> for FallbackTranslator<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.providers.iter_mut().for_each(|p| p.set_words(words))
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        broadcast(&mut self.providers, |p| p.set_from(language))?;
+        self.from = language;
+        Ok(())
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        broadcast(&mut self.providers, |p| p.set_to(language))?;
+        self.to = language;
+        Ok(())
+    }
+
+    fn set_appid(&mut self, _appid: &'a str) {}
+
+    fn set_secret_key(&mut self, _secret_key: &'a str) {}
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        broadcast(&mut self.providers, |p| p.set_domain(domain.clone()))?;
+        self.domain = domain;
+        Ok(())
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.providers.iter_mut().for_each(|p| p.set_max_length(l))
+    }
+
+    /// 所有接口中最小的最大长度，保证每个接口都能翻译
+    fn max_length(&self) -> usize {
+        self.providers
+            .iter()
+            .map(|p| p.max_length())
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn get_words(&self) -> &str {
+        self.providers.first().map_or("", |p| p.get_words())
+    }
+
+    fn from(&self) -> Language {
+        self.from
+    }
+
+    fn to(&self) -> Language {
+        self.to
+    }
+
+    fn appid(&self) -> &'a str {
+        ""
+    }
+
+    fn secret_key(&self) -> &'a str {
+        ""
+    }
+
+    fn domain(&self) -> DomainType {
+        self.domain.clone()
+    }
+
+    fn provider(&self) -> &'static str {
+        "Fallback"
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.last_provider.unwrap_or("Fallback")
+    }
+
+    /// 所有接口能力的合并
+    fn capabilities(&self) -> Capabilities {
+        self.providers
+            .iter()
+            .map(|p| p.capabilities())
+            .reduce(Capabilities::union)
+            .unwrap_or_else(|| Capabilities::unrestricted(0))
+    }
+
+    /// 各接口使用`fork()`复制，保留各自的appid和秘钥
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(FallbackTranslator::new(
+            self.providers.iter().map(|p| p.fork()).collect(),
+        ))
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        self.last_provider = None;
+        self.failures.clear();
+        for p in self.providers.iter_mut() {
+            // 不支持当前语言对或领域的接口
+            let supported = p
+                .capabilities()
+                .check(p.provider(), self.from, self.to, &self.domain);
+            if let Err(e) = supported {
+                self.failures.push((p.provider(), e));
+                continue;
+            }
+            match p.trans() {
+                Ok(res) => {
+                    self.last_provider = Some(p.served_by());
+                    return Ok(res);
+                }
+                Err(e) if e.is_provider_failure() => self.failures.push((p.served_by(), e)),
+                Err(e) => return Err(e),
+            }
+        }
+        // 全部失败，各接口的错误见`failures()`
+        let data = self
+            .failures
+            .iter()
+            .map(|(p, e)| format!("{}: {}", p, e.to_string().trim()))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Err(TransError::RequestError(
+            ErrInfoBuildle::new()
+                .model("FallbackTranslator")
+                .method("trans()")
+                .original(if self.providers.is_empty() {
+                    "no provider".to_owned()
+                } else {
+                    "all providers failed".to_owned()
+                })
+                .data(data)
+                .build(),
+        ))
+    }
+}
+
+/// 设置到所有接口，有一个接口接受即成功；都不接受时返回第一个错误
+fn broadcast<'a>(
+    providers: &mut [Box<dyn Translation<'a> + 'a>],
+    mut set: impl FnMut(&mut Box<dyn Translation<'a> + 'a>) -> Result<()>,
+) -> Result<()> {
+    let mut first_err = None;
+    let mut accepted = providers.is_empty();
+    for p in providers.iter_mut() {
+        match set(p) {
+            Ok(()) => accepted = true,
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+    match (accepted, first_err) {
+        (false, Some(e)) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::{capability::Capabilities, testing::Mock};
+
+    fn boxed(mock: Mock) -> Box<dyn Translation<'static>> {
+        Box::new(mock)
+    }
+
+    /// 只支持英译中的能力
+    fn en_zh() -> Capabilities {
+        Capabilities {
+            pairs: Some(vec![(Language::En, Language::Zh)]),
+            domains: Some(vec![DomainType::General]),
+            ..Capabilities::unrestricted(5000)
+        }
+    }
+
+    #[test]
+    fn falls_over_on_provider_failure() {
+        let first = Mock::new().named("First").failing(TransError::CircuitOpen);
+        let second = Mock::new().named("Second");
+        let (first_calls, second_calls) = (first.calls(), second.calls());
+        let mut t = FallbackTranslator::new(vec![boxed(first)]).then(boxed(second));
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "HELLO");
+        assert_eq!(t.served_by(), "Second");
+        assert_eq!(t.last_provider(), Some("Second"));
+        assert_eq!(t.failures().len(), 1);
+        assert_eq!(t.failures()[0].0, "First");
+        assert!(matches!(t.failures()[0].1, TransError::CircuitOpen(_)));
+        assert_eq!(first_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+
+        // 全部失败时返回RequestError，各接口的错误见failures()
+        t.set_words("fail");
+        assert!(matches!(t.trans(), Err(TransError::RequestError(_))));
+        assert_eq!(t.last_provider(), None);
+        assert_eq!(t.served_by(), "Fallback");
+        let failed: Vec<_> = t.failures().iter().map(|(p, _)| *p).collect();
+        assert_eq!(failed, ["First", "Second"]);
+    }
+
+    #[test]
+    fn returns_other_errors_at_once() {
+        let second = Mock::new().named("Second");
+        let calls = second.calls();
+        let mut t = FallbackTranslator::new(vec![
+            boxed(Mock::new().named("First").failing(TransError::Unsupported)),
+            boxed(second),
+        ]);
+        t.set_words("hello");
+        assert!(matches!(t.trans(), Err(TransError::Unsupported(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(t.last_provider(), None);
+    }
+
+    #[test]
+    fn skips_unsupported_providers() {
+        let narrow = Mock::new().named("Narrow").with_capabilities(en_zh());
+        let calls = narrow.calls();
+        let mut t = FallbackTranslator::new(vec![boxed(narrow), boxed(Mock::new().named("Wide"))]);
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "HELLO");
+        assert_eq!(t.served_by(), "Narrow");
+
+        t.set_from(Language::Fra).unwrap();
+        assert_eq!(t.trans().unwrap(), "HELLO");
+        assert_eq!(t.served_by(), "Wide");
+        assert!(matches!(
+            t.failures(),
+            [("Narrow", TransError::Unsupported(_))]
+        ));
+
+        t.set_from(Language::En).unwrap();
+        t.set_domain(DomainType::Medicine).unwrap();
+        t.trans().unwrap();
+        assert_eq!(t.served_by(), "Wide");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn broadcast_settings() {
+        let mut t = FallbackTranslator::new(vec![
+            boxed(Mock::new().named("First")),
+            boxed(Mock::new().named("Second")),
+        ]);
+        t.set_from(Language::De).unwrap();
+        t.set_to(Language::Fra).unwrap();
+        t.set_domain(DomainType::Finance).unwrap();
+        t.set_max_length(100);
+        t.set_words("hello");
+        for p in t.providers() {
+            assert_eq!((p.from(), p.to()), (Language::De, Language::Fra));
+            assert_eq!(p.domain(), DomainType::Finance);
+            assert_eq!(p.max_length(), 100);
+            assert_eq!(p.get_words(), "hello");
+        }
+
+        // 有一个接口接受即成功，都不接受时返回错误且不改变设置
+        let mut t = FallbackTranslator::new(vec![
+            boxed(Mock::new().with_capabilities(en_zh())),
+            boxed(Mock::new()),
+        ]);
+        t.set_from(Language::De).unwrap();
+        assert_eq!(t.providers()[0].from(), Language::En);
+        assert_eq!(t.providers()[1].from(), Language::De);
+        let mut t = FallbackTranslator::new(vec![boxed(Mock::new().with_capabilities(en_zh()))]);
+        assert!(matches!(
+            t.set_to(Language::De),
+            Err(TransError::Unsupported(_))
+        ));
+        assert_eq!(t.to(), Language::Zh);
+    }
+}
@@ -0,0 +1,576 @@
+//! 术语表
+//!
+//! 同一个术语在不同句子中常被翻译成不同的说法。翻译前把原文中的术语替换为占位符，
+//! 翻译后再把占位符替换为规定的译法；并检查译文中是否缺少规定的译法
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use quick_xml::{events::Event, Reader};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    protect::{mask, unmask, Marker},
+    tm::lang_key,
+    DomainType, Language, Result, Translation,
+};
+
+/// 一条术语
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    /// 原文术语
+    pub source: String,
+    /// 规定的译法
+    pub target: String,
+}
+
+/// 原文中出现了术语，但译文中没有规定的译法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermIssue {
+    /// 原文中出现的术语(原文中的写法)
+    pub found: String,
+    /// 术语表中的原文术语
+    pub source: String,
+    /// 规定的译法
+    pub target: String,
+}
+
+/// 术语表
+#[derive(Debug, Clone, Default)]
+pub struct Glossary {
+    terms: Vec<Term>,
+    case_sensitive: bool,
+    /// 匹配所有原文术语，长的优先
+    pattern: Option<Regex>,
+}
+
+impl Glossary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 读取CSV文件，每行`原文术语,译法`。`#`开头的行为注释，首行为`source,target`时视为表头
+    pub fn load_csv(path: impl AsRef<Path>) -> Result<Self> {
+        let mut g = Self::new();
+        g.merge_delimited(&read(path.as_ref())?, b',')?;
+        Ok(g)
+    }
+    /// 读取TSV文件，每行`原文术语<TAB>译法`。其余同`load_csv`
+    pub fn load_tsv(path: impl AsRef<Path>) -> Result<Self> {
+        let mut g = Self::new();
+        g.merge_delimited(&read(path.as_ref())?, b'\t')?;
+        Ok(g)
+    }
+    /// 读取TBX文件，取出`from`到`to`的术语。语言按`Language`匹配，如`en`匹配`en-US`，`zh-TW`匹配`zh-Hant`
+    pub fn load_tbx(path: impl AsRef<Path>, from: &str, to: &str) -> Result<Self> {
+        let mut g = Self::new();
+        g.merge_tbx(&read(path.as_ref())?, from, to)?;
+        Ok(g)
+    }
+    /// 解析以`delimiter`分隔的术语表并入当前术语表，返回读取的条数。
+    /// 失败时术语表保持不变
+    pub fn merge_delimited(&mut self, text: &str, delimiter: u8) -> Result<usize> {
+        self.update(|g| g.parse_delimited(text, delimiter))
+    }
+    fn parse_delimited(&mut self, text: &str, delimiter: u8) -> Result<usize> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(delimiter)
+            .comment(Some(b'#'))
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(text.as_bytes());
+        let mut count = 0;
+        for (i, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| glossary_err("merge_delimited()", e.to_string()))?;
+            let (Some(source), Some(target)) = (record.get(0), record.get(1)) else {
+                continue;
+            };
+            if i == 0
+                && source.eq_ignore_ascii_case("source")
+                && target.eq_ignore_ascii_case("target")
+            {
+                continue;
+            }
+            if self.push(source, target) {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+    /// 解析TBX内容并入当前术语表，返回读取的条数。失败时术语表保持不变
+    /// 同时支持TBX 2(termEntry/langSet/tig)和TBX 3(conceptEntry/langSec/termSec)
+    pub fn merge_tbx(&mut self, xml: &str, from: &str, to: &str) -> Result<usize> {
+        self.update(|g| g.parse_tbx(xml, from, to))
+    }
+    fn parse_tbx(&mut self, xml: &str, from: &str, to: &str) -> Result<usize> {
+        let (from, to) = (lang_key(from), lang_key(to));
+        let mut reader = Reader::from_str(xml);
+        let mut entry: HashMap<String, Vec<String>> = HashMap::default();
+        let mut lang = String::new();
+        let mut term: Option<String> = None;
+        let mut count = 0;
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| glossary_err("merge_tbx()", e.to_string()))?;
+            match event {
+                Event::Start(e) => match e.name().as_ref() {
+                    b"termEntry" | b"conceptEntry" => entry.clear(),
+                    b"langSet" | b"langSec" => {
+                        lang = e
+                            .attributes()
+                            .flatten()
+                            .find(|a| a.key.as_ref() == b"xml:lang" || a.key.as_ref() == b"lang")
+                            .and_then(|a| a.unescape_value().ok())
+                            .map(|v| lang_key(&v))
+                            .unwrap_or_default()
+                    }
+                    b"term" => term = Some(String::new()),
+                    _ => {}
+                },
+                Event::Text(t) => {
+                    if let Some(term) = term.as_mut() {
+                        let text = t
+                            .unescape()
+                            .map_err(|e| glossary_err("merge_tbx()", e.to_string()))?;
+                        term.push_str(&text);
+                    }
+                }
+                Event::End(e) => match e.name().as_ref() {
+                    b"term" => {
+                        if let Some(term) = term.take() {
+                            entry.entry(lang.clone()).or_default().push(term);
+                        }
+                    }
+                    b"termEntry" | b"conceptEntry" => {
+                        // 目标语言的第一个术语为规定译法，源语言的所有术语(含同义词)都替换为它
+                        if let (Some(sources), Some(target)) =
+                            (entry.get(&from), entry.get(&to).and_then(|t| t.first()))
+                        {
+                            for source in sources {
+                                if self.push(source, target) {
+                                    count += 1;
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(count)
+    }
+    /// 添加一条术语。失败时术语表保持不变
+    pub fn add(&mut self, source: &str, target: &str) -> Result<()> {
+        self.update(|g| {
+            g.push(source, target);
+            Ok(())
+        })
+    }
+    /// 是否区分大小写，默认不区分
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Result<Self> {
+        self.update(|g| {
+            g.case_sensitive = case_sensitive;
+            Ok(())
+        })?;
+        Ok(self)
+    }
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+    /// 原文中出现的术语: (在原文中的位置, 术语下标)
+    pub fn find(&self, text: &str) -> Vec<(std::ops::Range<usize>, usize)> {
+        let Some(pattern) = &self.pattern else {
+            return Vec::new();
+        };
+        pattern
+            .find_iter(text)
+            .filter_map(|m| self.term_of(m.as_str()).map(|i| (m.range(), i)))
+            .collect()
+    }
+    /// 把原文中的术语替换为占位符`[#0]`、`[#1]`...，返回替换后的文本和每个占位符对应的术语下标
+    pub fn protect(&self, text: &str) -> (String, Vec<usize>) {
+        let (ranges, used): (Vec<_>, Vec<_>) = self.find(text).into_iter().unzip();
+        (mask(text, ranges, Marker::Term), used)
+    }
+    /// 把译文中的占位符替换为规定的译法。接口可能在占位符中加入空格或改成全角括号，均可识别
+    pub fn restore(&self, output: &str, used: &[usize]) -> String {
+        let targets: Vec<String> = used.iter().map(|i| self.terms[*i].target.clone()).collect();
+        unmask(output, Marker::Term, &targets).0
+    }
+    /// 检查原文中出现的术语，译文中是否使用了规定的译法
+    pub fn verify(&self, source: &str, output: &str) -> Vec<TermIssue> {
+        let output_lower = output.to_lowercase();
+        let mut issues: Vec<TermIssue> = Vec::new();
+        for (range, i) in self.find(source) {
+            let term = &self.terms[i];
+            let present = if self.case_sensitive {
+                output.contains(&term.target)
+            } else {
+                output_lower.contains(&term.target.to_lowercase())
+            };
+            if !present && !issues.iter().any(|issue| issue.source == term.source) {
+                issues.push(TermIssue {
+                    found: source[range].to_owned(),
+                    source: term.source.clone(),
+                    target: term.target.clone(),
+                });
+            }
+        }
+        issues
+    }
+    fn push(&mut self, source: &str, target: &str) -> bool {
+        let (source, target) = (source.trim(), target.trim());
+        if source.is_empty() || target.is_empty() {
+            return false;
+        }
+        match self.terms.iter_mut().find(|t| t.source == source) {
+            Some(t) => t.target = target.to_owned(),
+            None => self.terms.push(Term {
+                source: source.to_owned(),
+                target: target.to_owned(),
+            }),
+        }
+        true
+    }
+    /// 修改术语表后重新编译正则。修改或编译失败时恢复原来的术语表
+    fn update<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let (terms, case_sensitive) = (self.terms.clone(), self.case_sensitive);
+        let res = f(self).and_then(|v| self.compile().map(|_| v));
+        if res.is_err() {
+            self.terms = terms;
+            self.case_sensitive = case_sensitive;
+        }
+        res
+    }
+    /// 术语过多时正则可能超出大小限制，返回`TransError::ConfigError`
+    fn compile(&mut self) -> Result<()> {
+        let mut terms: Vec<&Term> = self.terms.iter().collect();
+        terms.sort_by_key(|t| std::cmp::Reverse(t.source.chars().count()));
+        let alternatives: Vec<String> = terms.iter().map(|t| term_pattern(&t.source)).collect();
+        self.pattern = if alternatives.is_empty() {
+            None
+        } else {
+            let pattern = RegexBuilder::new(&alternatives.join("|"))
+                .case_insensitive(!self.case_sensitive)
+                .build()
+                .map_err(|e| {
+                    TransError::ConfigError(
+                        ErrInfoBuildle::new()
+                            .model("Glossary")
+                            .method("compile()")
+                            .original(e.to_string())
+                            .data(format!("术语数: {}", self.terms.len()))
+                            .build(),
+                    )
+                })?;
+            Some(pattern)
+        };
+        Ok(())
+    }
+    /// 匹配到的文本对应的术语下标
+    fn term_of(&self, found: &str) -> Option<usize> {
+        let found = found.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.terms.iter().position(|t| {
+            let source = t.source.split_whitespace().collect::<Vec<_>>().join(" ");
+            if self.case_sensitive {
+                source == found
+            } else {
+                source.to_lowercase() == found.to_lowercase()
+            }
+        })
+    }
+}
+
+/// 带术语表的翻译器
+/// `build()`/`fork()`出的翻译器与原翻译器共用同一个术语表
+pub struct GlossaryTranslator<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    glossary: Arc<Glossary>,
+    protect: bool,
+    issues: Vec<TermIssue>,
+}
+
+impl<'a> GlossaryTranslator<'a> {
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>, glossary: Glossary) -> Self {
+        GlossaryTranslator {
+            inner,
+            glossary: Arc::new(glossary),
+            protect: true,
+            issues: Vec::new(),
+        }
+    }
+    /// 是否在翻译前用占位符保护术语，默认开启。关闭时只做检查
+    pub fn protect(mut self, protect: bool) -> Self {
+        self.protect = protect;
+        self
+    }
+    pub fn glossary(&self) -> &Glossary {
+        &self.glossary
+    }
+    /// 上一次`trans()`的译文中缺少规定译法的术语
+    pub fn issues(&self) -> &[TermIssue] {
+        &self.issues
+    }
+}
+
+impl<'a> Translation<'a> for GlossaryTranslator<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(GlossaryTranslator {
+            inner: self.inner.build(),
+            glossary: self.glossary.clone(),
+            protect: self.protect,
+            issues: Vec::new(),
+        })
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        self.issues.clear();
+        let source = self.inner.get_words().to_owned();
+        let res = if self.protect {
+            let (masked, used) = self.glossary.protect(&source);
+            self.inner.set_words(&masked);
+            let res = self.inner.trans();
+            self.inner.set_words(&source);
+            self.glossary.restore(&res?, &used)
+        } else {
+            self.inner.trans()?
+        };
+        self.issues = self.glossary.verify(&source, &res);
+        Ok(res)
+    }
+}
+
+/// 术语的正则。以字母数字开头/结尾的术语要求在单词边界上，术语中的空白可匹配任意空白
+fn term_pattern(term: &str) -> String {
+    let body = term
+        .split_whitespace()
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"\s+");
+    let starts_word = term
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric());
+    let ends_word = term
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_ascii_alphanumeric());
+    format!(
+        "{}{}{}",
+        if starts_word { r"\b" } else { "" },
+        body,
+        if ends_word { r"\b" } else { "" }
+    )
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|e| {
+        TransError::IOError(
+            ErrInfoBuildle::new()
+                .model("Glossary")
+                .method("read()")
+                .original(e.to_string())
+                .data(path.display().to_string())
+                .build(),
+        )
+    })
+}
+
+fn glossary_err(method: &str, err: String) -> TransError {
+    TransError::IOError(
+        ErrInfoBuildle::new()
+            .model("Glossary")
+            .method(method)
+            .original(err)
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Mock;
+
+    fn glossary() -> Glossary {
+        let mut g = Glossary::new();
+        g.merge_delimited(
+            "source,target\n# 注释\nfusion,融合\n\"EWSR1 fusion\",EWSR1融合\nFAP,家族性腺瘤性息肉病\n,空\n",
+            b',',
+        )
+        .unwrap();
+        g
+    }
+
+    #[test]
+    fn parse_csv() {
+        let g = glossary();
+        let sources: Vec<&str> = g.terms().iter().map(|t| t.source.as_str()).collect();
+        assert_eq!(sources, ["fusion", "EWSR1 fusion", "FAP"]);
+        // 重复的术语以后一条为准
+        let mut g = g;
+        assert_eq!(g.merge_delimited("FAP\tFAP\n", b'\t').unwrap(), 1);
+        assert_eq!(g.len(), 3);
+        assert_eq!(g.terms()[2].target, "FAP");
+    }
+
+    #[test]
+    fn longest_match_first() {
+        let g = glossary();
+        let found: Vec<(&str, usize)> = {
+            let text = "EWSR1  fusion and fusion, not confusion or FAPs.";
+            g.find(text)
+                .into_iter()
+                .map(|(r, i)| (&text[r], i))
+                .collect()
+        };
+        assert_eq!(found, [("EWSR1  fusion", 1), ("fusion", 0)]);
+    }
+
+    #[test]
+    fn case_handling() {
+        let g = glossary();
+        assert_eq!(g.find("Fusion").len(), 1);
+        let g = g.case_sensitive(true).unwrap();
+        assert!(g.find("Fusion").is_empty());
+        assert_eq!(g.find("fusion").len(), 1);
+        assert_eq!(g.verify("fusion", "融合").len(), 0);
+
+        let g = glossary();
+        let issues = g.verify("Fusion of FAP", "融合");
+        assert_eq!(
+            issues,
+            [TermIssue {
+                found: "FAP".to_owned(),
+                source: "FAP".to_owned(),
+                target: "家族性腺瘤性息肉病".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn protect_and_restore() {
+        let g = glossary();
+        let (masked, used) = g.protect("EWSR1 fusion with FAP.");
+        assert_eq!(masked, "[#0] with [#1].");
+        assert_eq!(used, [1, 2]);
+        // 接口改写了占位符
+        assert_eq!(
+            g.restore("[ # 0 ]伴【#1】。", &used),
+            "EWSR1融合伴家族性腺瘤性息肉病。"
+        );
+    }
+
+    #[test]
+    fn failed_merge_keeps_terms() {
+        let mut g = glossary();
+        let err = g.merge_tbx(
+            "<tbx><termEntry><langSet xml:lang=\"en\"><term>CD99</term></langSet>\
+             <langSet xml:lang=\"zh\"><term>CD99抗原</term></langSet></termEntry></body>",
+            "en",
+            "zh",
+        );
+        assert!(err.is_err());
+        assert_eq!(g.len(), 3);
+        assert!(g.find("CD99").is_empty());
+    }
+
+    #[test]
+    fn tbx_synonyms_and_languages() {
+        let xml = r#"<tbx><text><body>
+            <termEntry>
+                <langSet xml:lang="en-US"><tig><term>tumour</term></tig><tig><term>tumor</term></tig></langSet>
+                <langSet xml:lang="zh-CN"><tig><term>肿瘤</term></tig></langSet>
+                <langSet xml:lang="zh-TW"><tig><term>腫瘤</term></tig></langSet>
+            </termEntry>
+        </body></text></tbx>"#;
+        let mut g = Glossary::new();
+        assert_eq!(g.merge_tbx(xml, "en", "zh-Hant").unwrap(), 2);
+        assert!(g.terms().iter().all(|t| t.target == "腫瘤"));
+    }
+
+    #[test]
+    fn translator_uses_targets() {
+        let mut t = GlossaryTranslator::new(Box::new(Mock::new()), glossary());
+        t.set_words("fusion of fap");
+        assert_eq!(t.trans().unwrap(), "融合 OF 家族性腺瘤性息肉病");
+        assert!(t.issues().is_empty());
+        assert_eq!(t.get_words(), "fusion of fap");
+    }
+}
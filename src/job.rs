@@ -0,0 +1,307 @@
+//! 可断点续传的长文档翻译任务
+//!
+//! 每翻译完一句，即把结果追加写入检查点文件(JSON lines)。
+//! 程序崩溃或额度用完后，使用同一个检查点文件重新运行，已成功翻译的句子不会重复翻译
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    document::{self, DocsOptions, ErrorPolicy},
+    err::{ErrInfoBuildle, TransError},
+    observer::Event,
+    Result, Translation,
+};
+
+/// 检查点文件中的一行，对应一个句子
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// 句子在文档中的下标
+    pub index: usize,
+    /// 原文
+    pub source: String,
+    /// 译文，失败时为None
+    pub translation: Option<String>,
+    /// 失败原因
+    pub error: Option<String>,
+    /// 本次调用翻译接口的次数
+    pub attempts: usize,
+}
+
+impl Record {
+    fn new(
+        index: usize,
+        source: &str,
+        result: std::result::Result<&str, &TransError>,
+        attempts: usize,
+    ) -> Self {
+        let (translation, error) = match result {
+            Ok(r) => (Some(r.to_owned()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Record {
+            index,
+            source: source.to_owned(),
+            translation,
+            error,
+            attempts,
+        }
+    }
+}
+
+/// 任务进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// 已完成(成功或失败)的句子数，包含从检查点恢复的句子
+    pub done: usize,
+    /// 句子总数
+    pub total: usize,
+    /// 失败的句子数
+    pub failed: usize,
+}
+
+/// 断点续传的文档翻译任务。句子切分方式同`docs()`
+pub struct Job {
+    path: PathBuf,
+    options: DocsOptions,
+}
+
+impl Job {
+    /// 使用`path`作为检查点文件，文件不存在时自动创建
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Job {
+            path: path.as_ref().to_path_buf(),
+            options: DocsOptions::default(),
+        }
+    }
+    /// 设置翻译选项(重试、并发、频率限制等)
+    /// `ErrorPolicy::FailFast`时遇到失败即停止并返回该错误，已完成的句子保留在检查点中
+    pub fn options(mut self, options: DocsOptions) -> Self {
+        self.options = options;
+        self
+    }
+    /// 检查点文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// 读取检查点。同一下标出现多次时，以最后一次为准
+    pub fn load(&self) -> Result<HashMap<usize, Record>> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::default()),
+            Err(e) => return Err(self.io_err("load()", e.to_string())),
+        };
+        let mut records = HashMap::default();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| self.io_err("load()", e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // 崩溃时最后一行可能只写了一半，忽略无法解析的行
+            if let Ok(record) = serde_json::from_str::<Record>(&line) {
+                records.insert(record.index, record);
+            }
+        }
+        Ok(records)
+    }
+    /// 运行任务。已在检查点中成功翻译且原文相同的句子直接使用检查点中的结果，
+    /// 其余句子(包括上次失败的)重新翻译。每完成一句调用一次`progress`
+    /// 返回按原文顺序排列的全部句子结果。
+    /// 写检查点失败时不再开始新的句子，等已开始的句子完成后返回`TransError::IOError`
+    pub fn run<'a, T>(
+        &self,
+        t: &mut T,
+        docs: &'a str,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<Vec<Record>>
+    where
+        T: Translation<'a> + ?Sized,
+    {
+        let sources = document::split(docs);
+        let mut records = self.load()?;
+        records.retain(|i, r| {
+            r.translation.is_some() && sources.get(*i).is_some_and(|s| *s == r.source)
+        });
+        let pending: Vec<usize> = (0..sources.len())
+            .filter(|i| !records.contains_key(i))
+            .collect();
+
+        let mut state = Progress {
+            done: sources.len() - pending.len(),
+            total: sources.len(),
+            failed: 0,
+        };
+        progress(state);
+        if pending.is_empty() {
+            return Ok(self.sorted(records));
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| self.io_err("run() open", e.to_string()))?;
+        self.repair_tail(&mut file)
+            .map_err(|e| self.io_err("run() open", e.to_string()))?;
+        let pending_sources: Vec<&'a str> = pending.iter().map(|i| sources[*i]).collect();
+        let mut write_err = None;
+        // 检查点写不进去时，之后的结果无法保存，不再开始新的句子，避免继续调用收费接口
+        let cancel = AtomicBool::new(false);
+        let outcomes = document::run_cancellable(
+            t,
+            &pending_sources,
+            &self.options,
+            &mut |event: &Event<'_>| {
+                let record = match *event {
+                    Event::SegmentTranslated {
+                        index,
+                        source,
+                        translation,
+                        attempts,
+                        ..
+                    } => Record::new(pending[index], source, Ok(translation), attempts),
+                    Event::SegmentFailed {
+                        index,
+                        source,
+                        error,
+                        attempts,
+                        ..
+                    } => Record::new(pending[index], source, Err(error), attempts),
+                    _ => return,
+                };
+                if write_err.is_none() {
+                    if let Err(e) = self.append(&mut file, &record) {
+                        write_err = Some(e);
+                        cancel.store(true, Ordering::SeqCst);
+                    }
+                }
+                state.done += 1;
+                if record.translation.is_none() {
+                    state.failed += 1;
+                }
+                progress(state);
+                records.insert(record.index, record);
+            },
+            &cancel,
+        );
+        if let Some(e) = write_err {
+            return Err(e);
+        }
+        if self.options.policy == ErrorPolicy::FailFast {
+            if let Some(e) = outcomes.into_iter().find_map(|o| o.result.err()) {
+                return Err(e);
+            }
+        }
+        Ok(self.sorted(records))
+    }
+    fn append(&self, file: &mut File, record: &Record) -> Result<()> {
+        let line = serde_json::to_string(record)
+            .map_err(|e| self.io_err("append() serialize", e.to_string()))?;
+        writeln!(file, "{}", line)
+            .and_then(|_| file.flush())
+            .map_err(|e| self.io_err("append() write", e.to_string()))
+    }
+    /// 上次写入中断时，文件末尾是半行。先补一个换行，避免新记录接在半行之后
+    fn repair_tail(&self, file: &mut File) -> std::io::Result<()> {
+        if file.metadata()?.len() == 0 {
+            return Ok(());
+        }
+        let mut reader = File::open(&self.path)?;
+        let mut last = [0u8; 1];
+        reader.seek(SeekFrom::End(-1))?;
+        reader.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            writeln!(file)?;
+        }
+        Ok(())
+    }
+    fn sorted(&self, records: HashMap<usize, Record>) -> Vec<Record> {
+        let mut records: Vec<Record> = records.into_values().collect();
+        records.sort_by_key(|r| r.index);
+        records
+    }
+    fn io_err(&self, method: &str, err: String) -> TransError {
+        TransError::IOError(
+            ErrInfoBuildle::new()
+                .model("Job")
+                .method(method)
+                .original(err)
+                .data(self.path.display().to_string())
+                .build(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_path, Mock};
+    use std::fs;
+
+    const DOCS: &str = "One. fail two. Three.";
+
+    #[test]
+    fn resume_skips_translated() {
+        let path = temp_path("job-resume.jsonl");
+        let job = Job::new(&path);
+        let mut t = Mock::new();
+        let calls = t.calls();
+        let records = job.run(&mut t, DOCS, |_| {}).unwrap();
+        let translations: Vec<_> = records.iter().map(|r| r.translation.as_deref()).collect();
+        assert_eq!(translations, [Some("ONE."), None, Some(" THREE.")]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // 只重新翻译上次失败的句子
+        let mut last = None;
+        let records = job.run(&mut t, DOCS, |p| last = Some(p)).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            last,
+            Some(Progress {
+                done: 3,
+                total: 3,
+                failed: 1
+            })
+        );
+
+        // 原文改变的句子重新翻译
+        let records = job.run(&mut t, "One. Two. Three.", |_| {}).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+        assert_eq!(records[1].translation.as_deref(), Some(" TWO."));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_ignores_partial_line() {
+        let path = temp_path("job-partial.jsonl");
+        let job = Job::new(&path);
+        job.run(&mut Mock::new(), "One. Two.", |_| {}).unwrap();
+        // 模拟写到一半时崩溃
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.truncate(content.len() - 10);
+        fs::write(&path, content).unwrap();
+        assert_eq!(job.load().unwrap().len(), 1);
+
+        let mut t = Mock::new();
+        let calls = t.calls();
+        let records = job.run(&mut t, "One. Two.", |_| {}).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(records.len(), 2);
+        // 补写的换行使新记录单独成行
+        assert_eq!(job.load().unwrap().len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_checkpoint_is_empty() {
+        let job = Job::new(temp_path("job-missing.jsonl"));
+        assert!(job.load().unwrap().is_empty());
+    }
+}
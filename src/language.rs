@@ -0,0 +1,447 @@
+//! 语言
+//!
+//! `Language`可由ISO 639-1(`ja`)、ISO 639-3(`jpn`)、BCP 47(`zh-CN`、`zh-Hant`、`pt-BR`)、
+//! 百度代码(`jp`)或英文名称(`Japanese`)解析，不区分大小写。
+//! 在不同标准中含义不同的代码(`slo`、`rom`)解析时报错。
+//! `Display`和serde使用BCP 47标签，各接口使用的代码见`Language::code`
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::err::{ErrInfoBuildle, TransError};
+
+struct Entry {
+    language: Language,
+    name: &'static str,
+    tag: &'static str,
+    iso639_1: Option<&'static str>,
+    iso639_3: &'static str,
+    baidu: &'static str,
+    deepl: Option<&'static str>,
+}
+
+macro_rules! languages {
+    ( $( $(#[$doc:meta])* $v:ident => ($name:literal, $tag:literal, $iso1:expr, $iso3:literal, $baidu:literal, $deepl:expr) ),* $(,)? ) => {
+        /// 语言类型
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Language {
+            $( $(#[$doc])* $v, )*
+            /// 自动检测
+            Auto,
+        }
+
+        /// (语言, 英文名称, BCP 47, ISO 639-1, ISO 639-3, 百度, DeepL)
+        const LANGUAGES: &[Entry] = &[
+            $(
+                Entry {
+                    language: Language::$v,
+                    name: $name,
+                    tag: $tag,
+                    iso639_1: $iso1,
+                    iso639_3: $iso3,
+                    baidu: $baidu,
+                    deepl: $deepl,
+                },
+            )*
+        ];
+    };
+}
+
+languages! {
+    /// 中文
+    Zh => ("Chinese", "zh", Some("zh"), "zho", "zh", Some("ZH")),
+    /// 英语
+    En => ("English", "en", Some("en"), "eng", "en", Some("EN")),
+    /// 日语
+    Jp => ("Japanese", "ja", Some("ja"), "jpn", "jp", Some("JA")),
+    /// 韩语
+    Kor => ("Korean", "ko", Some("ko"), "kor", "kor", Some("KO")),
+    /// 泰语
+    Th => ("Thai", "th", Some("th"), "tha", "th", None),
+    /// 越南语
+    Vie => ("Vietnamese", "vi", Some("vi"), "vie", "vie", None),
+    /// 俄语
+    Ru => ("Russian", "ru", Some("ru"), "rus", "ru", Some("RU")),
+    /// 繁体中文
+    Cht => ("Traditional Chinese", "zh-Hant", Some("zh"), "zho", "cht", Some("ZH-HANT")),
+    /// 粤语
+    Yue => ("Cantonese", "yue", None, "yue", "yue", None),
+    /// 文言文
+    Wyw => ("Classical Chinese", "lzh", None, "lzh", "wyw", None),
+    /// 法语
+    Fra => ("French", "fr", Some("fr"), "fra", "fra", Some("FR")),
+    /// 西班牙语
+    Spa => ("Spanish", "es", Some("es"), "spa", "spa", Some("ES")),
+    /// 阿拉伯语
+    Ara => ("Arabic", "ar", Some("ar"), "ara", "ara", Some("AR")),
+    /// 德语
+    De => ("German", "de", Some("de"), "deu", "de", Some("DE")),
+    /// 意大利语
+    It => ("Italian", "it", Some("it"), "ita", "it", Some("IT")),
+    /// 葡萄牙语
+    Pt => ("Portuguese", "pt", Some("pt"), "por", "pt", Some("PT")),
+    /// 希腊语
+    El => ("Greek", "el", Some("el"), "ell", "el", Some("EL")),
+    /// 荷兰语
+    Nl => ("Dutch", "nl", Some("nl"), "nld", "nl", Some("NL")),
+    /// 波兰语
+    Pl => ("Polish", "pl", Some("pl"), "pol", "pl", Some("PL")),
+    /// 保加利亚语
+    Bul => ("Bulgarian", "bg", Some("bg"), "bul", "bul", Some("BG")),
+    /// 爱沙尼亚语
+    Est => ("Estonian", "et", Some("et"), "est", "est", Some("ET")),
+    /// 丹麦语
+    Dan => ("Danish", "da", Some("da"), "dan", "dan", Some("DA")),
+    /// 芬兰语
+    Fin => ("Finnish", "fi", Some("fi"), "fin", "fin", Some("FI")),
+    /// 捷克语
+    Cs => ("Czech", "cs", Some("cs"), "ces", "cs", Some("CS")),
+    /// 罗马尼亚语
+    Rom => ("Romanian", "ro", Some("ro"), "ron", "rom", Some("RO")),
+    /// 斯洛文尼亚语
+    Slo => ("Slovenian", "sl", Some("sl"), "slv", "slo", Some("SL")),
+    /// 瑞典语
+    Swe => ("Swedish", "sv", Some("sv"), "swe", "swe", Some("SV")),
+    /// 匈牙利语
+    Hu => ("Hungarian", "hu", Some("hu"), "hun", "hu", Some("HU")),
+    /// 印尼语
+    Id => ("Indonesian", "id", Some("id"), "ind", "id", Some("ID")),
+    /// 马来语
+    May => ("Malay", "ms", Some("ms"), "msa", "may", None),
+    /// 土耳其语
+    Tr => ("Turkish", "tr", Some("tr"), "tur", "tr", Some("TR")),
+    /// 希伯来语
+    Heb => ("Hebrew", "he", Some("he"), "heb", "heb", None),
+    /// 波斯语
+    Per => ("Persian", "fa", Some("fa"), "fas", "per", None),
+    /// 印地语
+    Hi => ("Hindi", "hi", Some("hi"), "hin", "hi", None),
+    /// 乌克兰语
+    Ukr => ("Ukrainian", "uk", Some("uk"), "ukr", "ukr", Some("UK")),
+    /// 孟加拉语
+    Ben => ("Bengali", "bn", Some("bn"), "ben", "ben", None),
+    /// 乌尔都语
+    Urd => ("Urdu", "ur", Some("ur"), "urd", "urd", None),
+    /// 泰米尔语
+    Tam => ("Tamil", "ta", Some("ta"), "tam", "tam", None),
+    /// 泰卢固语
+    Tel => ("Telugu", "te", Some("te"), "tel", "tel", None),
+    /// 立陶宛语
+    Lit => ("Lithuanian", "lt", Some("lt"), "lit", "lit", Some("LT")),
+    /// 拉脱维亚语
+    Lav => ("Latvian", "lv", Some("lv"), "lav", "lav", Some("LV")),
+    /// 克罗地亚语
+    Hrv => ("Croatian", "hr", Some("hr"), "hrv", "hrv", None),
+    /// 塞尔维亚语
+    Srp => ("Serbian", "sr", Some("sr"), "srp", "srp", None),
+    /// 斯洛伐克语
+    Sk => ("Slovak", "sk", Some("sk"), "slk", "sk", Some("SK")),
+    /// 加泰罗尼亚语
+    Cat => ("Catalan", "ca", Some("ca"), "cat", "cat", None),
+    /// 菲律宾语
+    Fil => ("Filipino", "fil", None, "fil", "fil", None),
+    /// 斯瓦希里语
+    Swa => ("Swahili", "sw", Some("sw"), "swa", "swa", None),
+    /// 格鲁吉亚语
+    Geo => ("Georgian", "ka", Some("ka"), "kat", "geo", None),
+    /// 亚美尼亚语
+    Arm => ("Armenian", "hy", Some("hy"), "hye", "arm", None),
+    /// 阿塞拜疆语
+    Aze => ("Azerbaijani", "az", Some("az"), "aze", "aze", None),
+    /// 哈萨克语
+    Kaz => ("Kazakh", "kk", Some("kk"), "kaz", "kaz", None),
+    /// 蒙古语
+    Mon => ("Mongolian", "mn", Some("mn"), "mon", "mon", None),
+    /// 缅甸语
+    Bur => ("Burmese", "my", Some("my"), "mya", "bur", None),
+    /// 高棉语
+    Hkm => ("Khmer", "km", Some("km"), "khm", "hkm", None),
+    /// 老挝语
+    Lao => ("Lao", "lo", Some("lo"), "lao", "lao", None),
+    /// 尼泊尔语
+    Nep => ("Nepali", "ne", Some("ne"), "nep", "nep", None),
+    /// 僧伽罗语
+    Sin => ("Sinhala", "si", Some("si"), "sin", "sin", None),
+    /// 冰岛语
+    Ice => ("Icelandic", "is", Some("is"), "isl", "ice", None),
+    /// 爱尔兰语
+    Gle => ("Irish", "ga", Some("ga"), "gle", "gle", None),
+    /// 威尔士语
+    Wel => ("Welsh", "cy", Some("cy"), "cym", "wel", None),
+    /// 南非荷兰语
+    Afr => ("Afrikaans", "af", Some("af"), "afr", "afr", None),
+    /// 巴斯克语
+    Baq => ("Basque", "eu", Some("eu"), "eus", "baq", None),
+    /// 加利西亚语
+    Glg => ("Galician", "gl", Some("gl"), "glg", "glg", None),
+    /// 马耳他语
+    Mlt => ("Maltese", "mt", Some("mt"), "mlt", "mlt", None),
+    /// 阿尔巴尼亚语
+    Alb => ("Albanian", "sq", Some("sq"), "sqi", "alb", None),
+    /// 马其顿语
+    Mac => ("Macedonian", "mk", Some("mk"), "mkd", "mac", None),
+    /// 白俄罗斯语
+    Bel => ("Belarusian", "be", Some("be"), "bel", "bel", None),
+    /// 乌兹别克语
+    Uzb => ("Uzbek", "uz", Some("uz"), "uzb", "uzb", None),
+    /// 阿姆哈拉语
+    Amh => ("Amharic", "am", Some("am"), "amh", "amh", None),
+    /// 挪威语
+    Nor => ("Norwegian", "no", Some("no"), "nor", "nor", Some("NB")),
+}
+
+/// 不在表中的常见写法: ISO 639-2/B代码、旧代码、方言
+const ALIASES: &[(&str, Language)] = &[
+    ("chi", Language::Zh),
+    ("cmn", Language::Zh),
+    ("fre", Language::Fra),
+    ("ger", Language::De),
+    ("gre", Language::El),
+    ("dut", Language::Nl),
+    ("cze", Language::Cs),
+    ("rum", Language::Rom),
+    ("iw", Language::Heb),
+    ("in", Language::Id),
+    ("tl", Language::Fil),
+    ("nb", Language::Nor),
+    ("nn", Language::Nor),
+    ("nob", Language::Nor),
+];
+
+/// 在不同标准中指不同语言的代码，解析时报错，需改用无歧义的代码
+const AMBIGUOUS: &[(&str, &str)] = &[
+    // 百度为斯洛文尼亚语，ISO 639-2/B为斯洛伐克语
+    ("slo", "斯洛文尼亚语用`sl`，斯洛伐克语用`sk`"),
+    // 百度为罗马尼亚语，ISO 639-2为罗姆语
+    ("rom", "罗马尼亚语用`ro`"),
+];
+
+impl Language {
+    fn entry(self) -> Option<&'static Entry> {
+        LANGUAGES.iter().find(|e| e.language == self)
+    }
+    /// 除`Auto`外的所有语言
+    pub fn all() -> impl Iterator<Item = Language> {
+        LANGUAGES.iter().map(|e| e.language)
+    }
+    /// 英文名称
+    pub fn name(self) -> &'static str {
+        self.entry().map_or("Auto", |e| e.name)
+    }
+    /// BCP 47标签，如`zh`、`zh-Hant`、`ja`。`Auto`为`None`
+    pub fn tag(self) -> Option<&'static str> {
+        self.entry().map(|e| e.tag)
+    }
+    /// ISO 639-1代码。粤语、文言文等没有两字母代码的语言为`None`
+    pub fn iso639_1(self) -> Option<&'static str> {
+        self.entry().and_then(|e| e.iso639_1)
+    }
+    /// ISO 639-3代码
+    pub fn iso639_3(self) -> Option<&'static str> {
+        self.entry().map(|e| e.iso639_3)
+    }
+    /// 百度翻译的语言代码，`Auto`为`auto`
+    pub fn baidu(self) -> &'static str {
+        self.entry().map_or("auto", |e| e.baidu)
+    }
+    /// DeepL的源语言代码，不支持的语言和`Auto`为`None`
+    pub fn deepl(self) -> Option<&'static str> {
+        self.entry().and_then(|e| e.deepl)
+    }
+    /// DeepL的目标语言代码。DeepL已不再接受`PT`、`EN`作为目标语言，
+    /// 分别使用`PT-PT`、`EN-GB`(巴西葡萄牙语`PT-BR`、美式英语`EN-US`需直接传给接口)；其余同`deepl`
+    pub fn deepl_target(self) -> Option<&'static str> {
+        match self {
+            Language::Pt => Some("PT-PT"),
+            Language::En => Some("EN-GB"),
+            _ => self.deepl(),
+        }
+    }
+    /// 按`Translation::provider()`取接口使用的语言代码，接口不支持时为`None`。
+    /// 未知的接口使用BCP 47标签。DeepL为源语言代码，目标语言见`deepl_target`
+    pub fn code(self, provider: &str) -> Option<&'static str> {
+        match provider {
+            "Baidu" => Some(self.baidu()),
+            "DeepL" => self.deepl(),
+            // CNKI只支持中英互译
+            "CNKI" => match self {
+                Language::Zh | Language::En => self.tag(),
+                _ => None,
+            },
+            _ => self.tag(),
+        }
+    }
+}
+
+/// 将语言类型，翻译为字符串。百度api使用
+impl From<Language> for &str {
+    fn from(value: Language) -> Self {
+        value.baidu()
+    }
+}
+
+/// BCP 47标签，`Auto`为`auto`
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag().unwrap_or("auto"))
+    }
+}
+
+impl FromStr for Language {
+    type Err = TransError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_ascii_lowercase().replace('_', "-");
+        if lower == "auto" {
+            return Ok(Language::Auto);
+        }
+        let mut subtags = lower.split('-');
+        let primary = subtags.next().unwrap_or_default();
+        if let Some((code, hint)) = AMBIGUOUS.iter().find(|(code, _)| *code == primary) {
+            return Err(parse_err(format!("有歧义的语言代码: {}，{}", code, hint)));
+        }
+        let found = LANGUAGES
+            .iter()
+            .find(|e| e.tag.eq_ignore_ascii_case(&lower))
+            .or_else(|| {
+                LANGUAGES.iter().find(|e| {
+                    e.iso639_1 == Some(primary) || e.iso639_3 == primary || e.baidu == primary
+                })
+            })
+            .map(|e| e.language)
+            .or_else(|| {
+                ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == primary)
+                    .map(|(_, l)| *l)
+            })
+            .or_else(|| {
+                LANGUAGES
+                    .iter()
+                    .find(|e| e.name.eq_ignore_ascii_case(&lower))
+                    .map(|e| e.language)
+            });
+        match found {
+            // 繁体: zh-Hant、zh-TW、zh-HK、zh-MO
+            Some(Language::Zh) if subtags.any(|t| matches!(t, "hant" | "tw" | "hk" | "mo")) => {
+                Ok(Language::Cht)
+            }
+            Some(language) => Ok(language),
+            None => Err(parse_err(format!("不支持的语言: {}", s))),
+        }
+    }
+}
+
+fn parse_err(msg: String) -> TransError {
+    TransError::ConfigError(
+        ErrInfoBuildle::new()
+            .model("Language")
+            .method("from_str()")
+            .original(msg)
+            .build(),
+    )
+}
+
+impl Serialize for Language {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|e: TransError| serde::de::Error::custom(e.to_string().trim().to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Language {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        for (i, a) in LANGUAGES.iter().enumerate() {
+            for b in &LANGUAGES[i + 1..] {
+                assert_ne!(a.tag, b.tag);
+                assert_ne!(a.baidu, b.baidu);
+                assert!(a.name != b.name);
+                // 繁体中文与中文共用ISO代码
+                if (a.language, b.language) != (Language::Zh, Language::Cht) {
+                    assert_ne!(a.iso639_3, b.iso639_3, "{} {}", a.name, b.name);
+                    assert!(a.iso639_1.is_none() || a.iso639_1 != b.iso639_1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for language in Language::all().chain([Language::Auto]) {
+            assert_eq!(parse(&language.to_string()), language);
+            if !AMBIGUOUS.iter().any(|(code, _)| *code == language.baidu()) {
+                assert_eq!(parse(language.baidu()), language);
+            }
+            assert_eq!(parse(language.name()), language);
+            if let Some(iso) = language.iso639_3() {
+                // 繁体中文的zho解析为中文
+                let expected = match language {
+                    Language::Cht => Language::Zh,
+                    l => l,
+                };
+                assert_eq!(parse(iso), expected);
+            }
+            let json = serde_json::to_string(&language).unwrap();
+            assert_eq!(serde_json::from_str::<Language>(&json).unwrap(), language);
+        }
+    }
+
+    #[test]
+    fn tags_and_aliases() {
+        assert_eq!(parse("zh-CN"), Language::Zh);
+        assert_eq!(parse("ZH_tw"), Language::Cht);
+        assert_eq!(parse("zh-Hant-HK"), Language::Cht);
+        assert_eq!(parse("pt-BR"), Language::Pt);
+        assert_eq!(parse(" JPN "), Language::Jp);
+        assert_eq!(parse("ger"), Language::De);
+        assert_eq!(parse("rum"), Language::Rom);
+        assert_eq!(parse("slv"), Language::Slo);
+        assert_eq!(parse("slk"), Language::Sk);
+        assert_eq!(parse("iw"), Language::Heb);
+        assert_eq!(parse("nb"), Language::Nor);
+        assert!("klingon".parse::<Language>().is_err());
+    }
+
+    #[test]
+    fn ambiguous_codes_are_rejected() {
+        for code in ["slo", "ROM", "slo-SI"] {
+            assert!(
+                matches!(code.parse::<Language>(), Err(TransError::ConfigError(_))),
+                "{}",
+                code
+            );
+        }
+        // 百度仍使用自己的代码
+        assert_eq!(Language::Slo.code("Baidu"), Some("slo"));
+        assert_eq!(Language::Rom.code("Baidu"), Some("rom"));
+    }
+
+    #[test]
+    fn provider_codes() {
+        assert_eq!(Language::Jp.code("Baidu"), Some("jp"));
+        assert_eq!(Language::Auto.code("Baidu"), Some("auto"));
+        assert_eq!(Language::Cht.code("DeepL"), Some("ZH-HANT"));
+        assert_eq!(Language::Th.code("DeepL"), None);
+        assert_eq!(Language::Jp.code("CNKI"), None);
+        assert_eq!(Language::En.code("CNKI"), Some("en"));
+        assert_eq!(Language::Cht.code("InHouse"), Some("zh-Hant"));
+        assert_eq!(Language::Pt.deepl(), Some("PT"));
+        assert_eq!(Language::Pt.deepl_target(), Some("PT-PT"));
+        assert_eq!(Language::En.deepl_target(), Some("EN-GB"));
+        assert_eq!(Language::De.deepl_target(), Some("DE"));
+    }
+}
@@ -3,10 +3,13 @@
 //! 现阶段一共封装了[CNKI API](https://dict.cnki.net/)和[百度翻译API](https://fanyi-api.baidu.com/)，
 //! CNKI为逆向的其网页版*不稳定*，百度使用的官方API，需要APIID*稳定*,使用量大需付费
 //!
+//! 从0.1升级: `Translation`的设置方法改为返回`Result`等不兼容的变更及迁移方法见CHANGELOG.md
+//!
 //! # Example
 //!
 //! ### CNKI 单句翻译
 //! ```no_run
+//! # use translation_api::*;
 //! let words = "A 55-year-old man presented for preoperative assessment following diagnosis of a dysplastic duodenal polyp on a background of familial adenomatous polyposis (FAP). The patient had prior surgical history of subtotal colectomy with ileorectal anastomosis with formation of ileo-anal pouch in 1981 with subsequent stoma reversal in 2000 (more than 20 years earlier).";
 //!
 //! let check_res = r#"患者男，55岁，因"家族性腺瘤性息肉病( FAP ) "背景下十二指肠异型增生性息肉就诊。患者既往有结肠次全切除回直肠吻合术史，1981年形成回肛袋，2000年(早了20多年)行造口还纳术。"#;
@@ -52,8 +55,515 @@
 //!
 //! 若有更精确的需求，请自行实现，或提issue
 //!
+//! ### 保留排版的文档翻译
+//! ```no_run
+//! # use translation_api::*;
+//! let docs = "Findings:\n\n  - The lesion shows diffusion restriction. Histopathology reports suggest a poorly differentiated malignancy.\n  - FISH is positive for EWSR1 gene re-arrangement.\n";
+//! let mut cnki = Translate::Cnki.create();
+//! let document = cnki.translate_document(docs);
+//! // 对齐的原文/译文
+//! for seg in &document.segments {
+//!     println!("{} => {}", seg.source, seg.translation);
+//! }
+//! // 按原文的段落、换行、缩进、列表符号重建的译文
+//! println!("{}", document.text());
+//! ```
+//! 段落、空行、行首缩进、列表符号(`-`、`*`、`•`、`1.`、`2)`、`3、`等)和句间空白原样保留，只翻译其中的句子。
+//!
+//! ### 获取失败原因 / 指定失败处理方式
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::document::*;
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! let options = DocsOptions {
+//!     policy: ErrorPolicy::KeepSource, // FailFast | Skip | KeepSource | Placeholder(String)
+//!     retries: 2,
+//!     ..Default::default()
+//! };
+//! for outcome in baidu.docs_detailed(docs, &options) {
+//!     match outcome.result {
+//!         Ok(r) => println!("{} => {}", outcome.source, r),
+//!         Err(e) => println!("{} 失败({}次): {}", outcome.source, outcome.attempts, e),
+//!     }
+//! }
+//! // 按策略处理失败的句子, FailFast时返回第一个错误
+//! let res = baidu.docs_with(docs, &options);
+//! ```
+//! `docs()`等价于使用`ErrorPolicy::Placeholder(" ---translation err--- ")`的`docs_with`。
+//!
+//! ### 并发文档翻译
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::{document::*, limit::RateLimit};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let mut cnki = Translate::Cnki.create();
+//! let options = DocsOptions {
+//!     workers: 4,                                 // 4个线程同时翻译
+//!     rate_limit: Some(RateLimit::per_second(2.0)?), // 所有线程合计每秒最多2次请求
+//!     ..Default::default()
+//! };
+//! // 结果仍按原文顺序返回
+//! let res = cnki.docs_with(docs, &options);
+//! # Ok(())
+//! # }
+//! ```
+//! 每个线程使用`fork()`复制出的翻译器(appid、秘钥、语言、领域、最大长度与原翻译器相同)。
+//!
+//! ### 断点续传的长文档翻译
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::{document::*, job::Job};
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! let job = Job::new("report-001.jsonl").options(DocsOptions {
+//!     policy: ErrorPolicy::FailFast, // 额度用完时立即停止，下次从断点继续
+//!     ..Default::default()
+//! });
+//! let records = job.run(baidu.as_mut(), docs, |p| {
+//!     println!("{}/{} 失败:{}", p.done, p.total, p.failed);
+//! });
+//! ```
+//! 每翻译完一句即追加写入检查点文件(JSON lines)。使用同一个检查点文件再次运行时，已成功翻译且原文未变的句子直接使用检查点中的结果，只翻译剩余和上次失败的句子。
+//!
+//! ### 翻译进度/事件回调
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::{document::*, observer::Event};
+//! # let docs = "";
+//! let mut cnki = Translate::Cnki.create();
+//! let mut on_event = |event: &Event| match event {
+//!     Event::SegmentStarted { index, total, .. } => println!("{}/{}", index + 1, total),
+//!     Event::SegmentTranslated { index, translation, elapsed, .. } => {
+//!         println!("#{} {:?} {}", index, elapsed, translation)
+//!     }
+//!     Event::SegmentFailed { index, error, .. } => println!("#{} 失败 {}", index, error),
+//!     Event::Retrying { index, attempt, .. } => println!("#{} 第{}次尝试", index, attempt),
+//!     Event::Finished { translated, failed, elapsed, .. } => {
+//!         println!("完成 {} 失败 {} 用时 {:?}", translated, failed, elapsed)
+//!     }
+//! };
+//! let outcomes = cnki.docs_observed(docs, &DocsOptions::default(), &mut on_event);
+//! ```
+//! 闭包`FnMut(&Event)`或实现了`Observer`的类型均可作为观察者。并发翻译时，事件同样在调用线程上回调。
+//!
+//! ### 翻译缓存
+//! ```no_run
+//! # use std::time::Duration;
+//! # use translation_api::*;
+//! # use translation_api::cache::CachedTranslator;
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! // 最多缓存10000句，24小时过期
+//! let mut cached = CachedTranslator::new(baidu, 10_000).ttl(Duration::from_secs(86400));
+//! let res = cached.docs(docs);
+//! println!("{:?}", cached.stats()); // CacheStats { hits, misses }
+//! ```
+//! 按(接口, 源语言, 目标语言, 领域, 去掉多余空白后的原文)缓存，重复的句子不再重复计费。只缓存翻译成功的结果。
+//!
+//! ### 持久化翻译缓存(SQLite)
+//! ```no_run
+//! # use std::time::Duration;
+//! # use translation_api::*;
+//! # use translation_api::cache::{CachedTranslator, SqliteCache};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! let cache = SqliteCache::open("translations.db")?;
+//! let mut cached = CachedTranslator::with_cache(baidu, cache);
+//! let res = cached.docs(docs);
+//!
+//! // 导出/导入(JSON lines)
+//! cached.cache().export("translations.jsonl")?;
+//! // 清理30天前的缓存
+//! cached.cache().evict_older_than(Duration::from_secs(30 * 86400))?;
+//! # Ok(())
+//! # }
+//! ```
+//! 缓存保存原文、译文、语言、领域、接口和写入时间，重启后仍然有效，同一台机器上的多个任务可共用一个文件。需要`sqlite` feature(默认开启)。
+//! 自定义存储实现`cache::TransCache`即可。
+//!
+//! ### 翻译记忆库(TMX)
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::tm::{TmTranslator, TranslationMemory};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! let tm = TranslationMemory::load("approved.tmx")?;
+//! // 相似度不低于0.95的句子直接使用记忆库中的译文，默认只用完全匹配
+//! let mut translator = TmTranslator::new(baidu, tm).threshold(0.95);
+//! let res = translator.docs(docs);
+//! // 接口新翻译的句子对导出为TMX，交给审校人员审核
+//! translator.memory().export_new("to-review.tmx")?;
+//! # Ok(())
+//! # }
+//! ```
+//! 支持TMX 1.4，语言按`Language`匹配(`zh-CN`与`zh`视为相同，`zh-TW`与`zh-Hant`为繁体中文)。模糊匹配按字符编辑距离计算相似度。
+//!
+//! ### 术语表
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::glossary::{Glossary, GlossaryTranslator};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! // 每行`原文,译文`，也可用`Glossary::load_tsv`、`Glossary::load_tbx("terms.tbx", "en", "zh")`
+//! let glossary = Glossary::load_csv("terms.csv")?;
+//! let mut translator = GlossaryTranslator::new(baidu, glossary);
+//! let res = translator.docs(docs);
+//! // 译文中没有使用指定译法的术语
+//! for issue in translator.issues() {
+//!     println!("{} -> {}", issue.source, issue.target);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//! 翻译前术语被替换为占位符，翻译后再换回指定译文，接口无法改写术语。默认不区分大小写，按最长匹配。
+//!
+//! ### 不翻译内容保护
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::protect::{Protector, ProtectedTranslator};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! // 内置规则: 网址、邮箱、带单位的数字、基因/蛋白符号；可追加自定义正则
+//! let protector = Protector::new().pattern(r"\bp?T[0-4][a-c]?N[0-3]M[01]\b")?;
+//! let mut translator = ProtectedTranslator::new(baidu, protector);
+//! translator.set_words("EWSR1-FLI1 fusion, CD99 positive on T1W, 5 mg/kg");
+//! let res = translator.trans()?;
+//! // 接口丢失或重复的占位符
+//! if !translator.report().is_clean() {
+//!     println!("{:?}", translator.report());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//! `EWSR1`、`NKX 2.2`、`CD99`、`T1W`、`5 mg/kg`等被替换为`[@0]`形式的占位符，翻译后换回原文。可与术语表叠加使用。
+//!
+//! ### 多接口自动切换
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::document::DocsOptions;
+//! # use translation_api::fallback::FallbackTranslator;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let cnki = Translate::Cnki.create();
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! // 先用CNKI，请求失败或接口报错时换Baidu
+//! let mut translator = FallbackTranslator::new(vec![cnki, baidu]);
+//! translator.set_words("EWSR1-FLI1 fusion was detected.");
+//! let res = translator.trans()?;
+//! println!("{:?}", translator.last_provider()); // Some("CNKI") 或 Some("Baidu")
+//! // 文档翻译时，每一句由哪个接口翻译见`SegmentOutcome::provider`
+//! let outcomes = translator.docs_detailed(docs, &DocsOptions::default());
+//! # Ok(())
+//! # }
+//! ```
+//! 语言、领域等设置会同步到所有接口；appid和秘钥需在加入前对各接口分别设置。
+//!
+//! ### 熔断
+//! ```no_run
+//! # use std::time::Duration;
+//! # use translation_api::*;
+//! # use translation_api::breaker::CircuitBreaker;
+//! # use translation_api::fallback::FallbackTranslator;
+//! # let docs = "";
+//! let cnki = CircuitBreaker::new(Translate::Cnki.create())
+//!     .window(20) // 统计最近20次调用
+//!     .min_calls(5)
+//!     .failure_ratio(0.5) // 失败比例达到50%时熔断
+//!     .cooldown(Duration::from_secs(60)); // 60秒后放行一次试探调用
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! // CNKI熔断期间直接返回`TransError::CircuitOpen`，由Baidu翻译
+//! let mut translator = FallbackTranslator::new(vec![Box::new(cnki), baidu]);
+//! let res = translator.docs(docs);
+//! ```
+//!
+//! ### Baidu多账号轮换
+//! ```no_run
+//! # use std::time::Duration;
+//! # use translation_api::*;
+//! # use translation_api::document::DocsOptions;
+//! # use translation_api::pool::{BaiduPool, Strategy};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let pool = BaiduPool::new(vec![("appid1", "secret1"), ("appid2", "secret2")])
+//!     .strategy(Strategy::LeastUsed) // 默认 Strategy::RoundRobin
+//!     .cooldown(Duration::from_secs(3600));
+//! let mut translator: Box<dyn Translation> = Box::new(pool);
+//! let res = translator.docs_with(docs, &DocsOptions { workers: 4, ..Default::default() })?;
+//! # Ok(())
+//! # }
+//! ```
+//! 账号返回54004(余额不足)、58001、52003(未授权)时暂停使用`cooldown`，当前句子换下一个账号重新翻译。
+//! `BaiduPool::usage()`返回各账号的调用次数、翻译字符数、失败和暂停情况。
+//!
+//! ### 用量统计与预算
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::meter::{current_month, Budget, MeteredTranslator, Price, UsageMeter};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! // 用量按 接口/账号/月 保存在usage.json中，默认价格表包含Baidu(每月50万免费字符，49元/百万字符)
+//! let meter = UsageMeter::open("usage.json")?
+//!     .price("Baidu", Price { free_chars: 500_000, per_million: 49.0 })
+//!     .budget("Baidu", Budget::Cost(100.0)); // 本月估算费用达到100元后不再翻译
+//! let mut translator = MeteredTranslator::new(baidu, meter);
+//! let res = translator.docs(docs);
+//! let cost = translator.meter().cost("Baidu", &current_month());
+//! # Ok(())
+//! # }
+//! ```
+//! 超出预算时返回`TransError::BudgetExceeded`，与`FallbackTranslator`一起使用时会换下一个接口。
+//! 统计文件只供一个进程使用。保存失败时译文照常返回，错误见`MeteredTranslator::save_error()`。
+//!
+//! ### 中间件
+//! ```no_run
+//! # use std::time::Duration;
+//! # use translation_api::*;
+//! # use translation_api::err::TransError;
+//! # use translation_api::middleware::*;
+//! # let docs = "";
+//! /// 自定义中间件: 调用前/调用后钩子
+//! struct Log;
+//! impl Middleware for Log {
+//!     fn before(&self, request: &mut Request) -> Result<Option<String>, TransError> {
+//!         println!("{} <= {}", request.provider, request.words);
+//!         Ok(None)
+//!     }
+//!     fn after(&self, _request: &Request, result: Result<String, TransError>) -> Result<String, TransError> {
+//!         println!("=> {:?}", result.as_ref().ok());
+//!         result
+//!     }
+//! }
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_appid("xxxxxxxxxxxx");
+//! baidu.set_secret_key("xxxxxxxxxxxxxx");
+//! let metrics = Metrics::new();
+//! // 后加入的层在外层: metrics -> cache -> log -> retry -> baidu
+//! let mut translator = ProviderBuilder::new(baidu)
+//!     .with(Retry::new(3).backoff(Duration::from_millis(500)))
+//!     .hook(Log)
+//!     .with(Cache::new(10_000))
+//!     .with(metrics.clone())
+//!     .build();
+//! let res = translator.docs(docs);
+//! println!("{:?}", metrics.snapshot());
+//! ```
+//! 内置层: `Retry`、`Cache`、`Metrics`、`RateLimit`。闭包也可以作为层使用，如`.with(|t| Box::new(GlossaryTranslator::new(t, glossary)))`。
+//!
+//! ### 经中间语言转译
+//! CNKI和Baidu领域翻译只支持中英互译。日文、俄文病例可先由支持日文的通用接口译为英文，再由Baidu医学领域译为中文，
+//! 中间译文和最终译文都保留。第一个接口需支持源语言(`Baidu`只能译中英)，这里的`in_house()`代表自己实现`Translation`的接口:
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::pivot::PivotTranslator;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # fn in_house() -> Box<dyn Translation<'static>> { Box::new(cnkis::CNKI::default_new()) }
+//! let mut general = in_house();
+//! general.set_from(Language::Jp)?;
+//! let mut medical = Translate::Baidu.create();
+//! medical.set_appid("appid");
+//! medical.set_secret_key("secret_key");
+//! medical.set_domain(DomainType::Medicine)?;
+//! // 日 => 英(自有接口) => 中(Baidu医学)
+//! let mut pivot = PivotTranslator::new(general, medical, Language::En)?;
+//! pivot.set_words("患者は発熱を訴えた。");
+//! let res = pivot.trans_pivot()?;
+//! println!("{}\n{}", res.intermediate, res.translation);
+//! # Ok(())
+//! # }
+//! ```
+//! `docs_pivot`逐句翻译文档，每句保留中间译文。
+//!
+//! ### 接口能力
+//! `capabilities()`返回接口支持的语言对、领域、最大长度、是否支持批量和需要的凭据。
+//! 设置接口不支持的语言或领域时返回`TransError::Unsupported`，不会被替换为其他语言:
+//! ```
+//! # use translation_api::*;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! let mut cnki = Translate::Cnki.create();
+//! assert!(cnki.capabilities().supports_pair(Language::En, Language::Zh));
+//! assert!(cnki.set_to(Language::Jp).is_err()); // CNKI只支持中英互译
+//! cnki.set_from(Language::Zh)?; // 中 => 英
+//! # Ok(())
+//! # }
+//! ```
+//! `FallbackTranslator`会跳过不支持当前语言对或领域的接口。
+//!
+//! ### 领域
+//! `DomainType`之外的接口领域标识使用`DomainType::Custom`原样传给接口，如Baidu新增的`law`、`news`、`senimed`(见`baidu::BAIDU_DOMAINS`)。
+//! `FromStr`将`custom:<标识>`解析为`Custom`，配置文件、命令行、HTTP服务的`domain`均可使用，其他未知名称报错；接口不支持时`set_domain`返回`TransError::Unsupported`:
+//! ```
+//! # use translation_api::*;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! let mut baidu = Translate::Baidu.create();
+//! baidu.set_domain("custom:law".parse()?)?; // DomainType::Custom("law")
+//! assert!("law".parse::<DomainType>().is_err());
+//! assert!(baidu.set_domain(DomainType::Custom("legal".to_owned())).is_err());
+//! assert_eq!(DomainType::Medicine.code("Baidu"), Some("medicine"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ### 语言
+//! `Language`可由ISO 639-1、ISO 639-3、BCP 47、百度代码或英文名称解析，不区分大小写；`Display`和serde使用BCP 47标签。
+//! `slo`、`rom`在百度代码与ISO 639-2中指不同的语言，解析时报错，请改用`sl`、`sk`、`ro`。
+//! 各接口使用的代码不同，`Language::code`按接口名称取出:
+//! ```
+//! # use translation_api::Language;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! let lang: Language = "zh-TW".parse()?; // Language::Cht
+//! assert_eq!(lang.to_string(), "zh-Hant");
+//! assert_eq!("ja".parse::<Language>()?, Language::Jp);
+//! assert_eq!(Language::Jp.code("Baidu"), Some("jp"));
+//! assert_eq!(Language::Jp.code("DeepL"), Some("JA"));
+//! assert_eq!(Language::Pt.deepl_target(), Some("PT-PT")); // DeepL目标语言不再接受PT
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ### 语言检测
+//! 离线检测文本的语言(按文字和字符三元组，不访问网络)，返回语言和置信度:
+//! ```
+//! # use translation_api::*;
+//! # use translation_api::detect::detect_language;
+//! let d = detect_language("Пациент жалуется на боль в груди.").unwrap();
+//! assert_eq!(d.language, Language::Ru);
+//! println!("{:.2}", d.confidence);
+//! ```
+//! CNKI不能自动检测源语言。文档翻译时设置`DocsOptions::detect_language`，逐句按检测结果设置源语言，
+//! 中英混排的文档中文句子译为英文、英文句子译为中文:
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::document::*;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! # let docs = "";
+//! let mut cnki = Translate::Cnki.create();
+//! let options = DocsOptions { detect_language: true, ..Default::default() };
+//! let res = cnki.docs_with(docs, &options)?;
+//! # Ok(())
+//! # }
+//! ```
+//! 中文叙述夹杂英文化验结果的文档，同时设置`passthrough_target`逐句分流: 已是目标语言的句子原样保留、不调用接口，
+//! 其余句子按检测到的语言设置源语言后翻译:
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::document::*;
+//! # let docs = "";
+//! # let mut cnki = Translate::Cnki.create();
+//! let options = DocsOptions { detect_language: true, passthrough_target: true, ..Default::default() };
+//! for o in cnki.docs_detailed(docs, &options) {
+//!     println!("{} {:?}", o.provider, o.result); // 原样保留的句子provider为`PASSTHROUGH`
+//! }
+//! ```
+//!
+//! ### 自定义接口
+//! 自己的`Translation`实现按名称注册后，与CNKI、Baidu一样由配置文件的`provider`、`Translate::from_config`、`FallbackTranslator`使用。
+//! 工厂函数的参数为配置中的`endpoint`，appid、秘钥、语言、领域等在创建后设置:
+//! ```no_run
+//! # use translation_api::*;
+//! # use translation_api::{fallback::FallbackTranslator, registry};
+//! # use translation_api::cnkis::CNKI;
+//! # struct InHouseMT;
+//! # impl InHouseMT { fn new<'a>(endpoint: &str) -> CNKI<'a> { let mut t = CNKI::default_new(); t.set_endpoint(endpoint); t } }
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! fn in_house(endpoint: Option<&str>) -> Box<dyn Translation<'_> + '_> {
+//!     Box::new(InHouseMT::new(endpoint.unwrap_or("http://mt.internal:8000")))
+//! }
+//! registry::register("in-house", in_house)?;
+//! // 配置文件中`provider = "in-house"`即可
+//! let mut translator = FallbackTranslator::new(vec![registry::create("in-house")?, registry::create("cnki")?]);
+//! # Ok(())
+//! # }
+//! ```
+//! `Profile::has_credentials`按接口`capabilities()`中的`auth`判断凭据是否齐全。
+//! 注册后在自己的`main`中调用`cli::run(std::env::args_os())`(`cli` feature)或`server::run`、`server::serve`(`server` feature)，即得到能使用该接口的`translate`命令行工具和HTTP服务。
+//!
+//! ### 配置文件
+//! 需要`config` feature(默认开启)。在TOML文件中按名称配置多组接口，字符串中的`${NAME}`替换为环境变量:
+//! ```toml
+//! default = "baidu-medical"
+//!
+//! [profiles.baidu-medical]
+//! provider = "baidu"
+//! appid = "${BAIDU_APPID}"
+//! secret_key = "${BAIDU_SECRET_KEY}"
+//! endpoint = "https://fanyi-api.baidu.com/api/trans/vip/fieldtranslate"
+//! from = "en"
+//! to = "zh"
+//! domain = "medicine"
+//! max_len = 1500
+//! qps = 1.0
+//! ```
+//! 环境变量`TRANSLATION_<PROFILE>_<KEY>`覆盖文件中的设置，如`TRANSLATION_BAIDU_MEDICAL_SECRET_KEY`；`TRANSLATION_PROFILE`指定默认profile。
+//! 以接口名`cnki`、`baidu`命名的profile不写在文件中也可使用。仍未设置凭据时读取0.1的`BAIDU_APPID`、`BAIDU_SECRET_KEY`、`CNKI_SECRET_KEY`。
+//! ```no_run
+//! # use translation_api::{config::Config, Translate};
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! let config = Config::load("translation.toml")?;
+//! let profile = config.profile("baidu-medical")?;
+//! let mut t = Translate::from_config(&profile)?;
+//! t.set_words("Hello world.");
+//! println!("{}", t.trans()?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ### 命令行工具
+//! 需要`cli` feature: `cargo install translation_api --features cli`
+//! ```text
+//! translate text "The lesion shows diffusion restriction."
+//! cat report.txt | translate --profile baidu --format bilingual stdin
+//! translate --config translation.toml --to zh --format jsonl file a.txt b.txt -o out/
+//! translate --detect --passthrough file mixed.txt
+//! ```
+//! 输出格式: `plain`(保留原文排版)、`bilingual`(逐句对照)、`jsonl`(每句一行JSON，含错误和接口)。
+//! `--profile`(或0.1的`--provider`)为配置文件(`--config`或环境变量`TRANSLATION_CONFIG`)中的profile或接口名，`--from`、`--to`、`--domain`优先于profile中的设置。
+//! `--detect`逐句检测源语言，`--passthrough`原样输出已是目标语言的句子。
+//!
+//! ### HTTP服务
+//! 需要`server` feature: `cargo install translation_api --features server --bin translation-server`
+//! 凭据保存在服务端，其他服务通过HTTP调用，缓存和限流在所有请求间共用:
+//! ```text
+//! translation-server --config translation.toml
+//! curl -d '{"text":"Hello world.","provider":"baidu-medical","from":"en","to":"zh"}' localhost:8080/translate
+//! ```
+//! 接口: `POST /translate`、`POST /translate/batch`(`{"texts": [..]}`)、`POST /documents`(保留排版)、`GET /providers`、`GET /health`。
+//! 请求中的`provider`为profile名称，可同时指定`from`、`to`、`domain`；批量和文档翻译指定`"mixed": true`时逐句按语言分流。服务设置与profile写在同一个文件中:
+//! ```toml
+//! addr = "127.0.0.1:8080"
+//! cache = 10000
+//! cache_ttl = 86400
+//! ```
+//!
 //! ### Baidu API
 //! ```no_run
+//! # use translation_api::*;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
 //! let words = "A 55-year-old man presented for preoperative assessment following diagnosis of a dysplastic duodenal polyp on a background of familial adenomatous polyposis (FAP). The patient had prior surgical history of subtotal colectomy with ileorectal anastomosis with formation of ileo-anal pouch in 1981 with subsequent stoma reversal in 2000 (more than 20 years earlier).";
 //!
 //! let check_res = r#"一名55岁的男性在家族性腺瘤性息肉病（FAP）的背景下诊断为十二指肠息肉增生后进行术前评估。该患者曾于1981年接受结肠次全切除术，回肠直肠吻合术并形成回肠肛囊，随后于2000年（20多年前）造口逆转。"#;
@@ -61,9 +571,9 @@
 //!
 //! baidu.set_appid("xxxxxxxxxxxx"); // 必须
 //! baidu.set_secret_key("xxxxxxxxxxxx"); // 必须
-//! baidu.set_from(Language::Auto); // default:Language::Auto
-//! baidu.set_to(Language::Zh); // default:Language::Zh
-//! baidu.set_domain(DomainType::Medicine); // default:DomainType::Medicine
+//! baidu.set_from(Language::Auto)?; // default:Language::Auto
+//! baidu.set_to(Language::Zh)?; // default:Language::Zh
+//! baidu.set_domain(DomainType::Medicine)?; // default:DomainType::Medicine
 //! baidu.set_words(words);
 //!
 //! match baidu.trans() {
@@ -74,12 +584,16 @@
 //!     Err(e) => println!("Err:{}", e),
 //! }
 //!  // BAIDU_APPID & BAIDU_secret_key 请自行前往官网注册。 垂直领域，每月50万免费字符,超出后仅收取超出部分费用，49元/百万字符
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! 支持多种翻译。默认英文=>中文。
 //!
 //! #### Baidu文档类型
 //! ```no_run
+//! # use translation_api::*;
+//! # fn main() -> Result<(), translation_api::err::TransError> {
 //! let docs = "Left masticator space shows an altered signal intensity heterogeneous expansile soft tissue mass, showing iso-intense signal intensity on T1W, heterogeneously hyperintense to intermediate signal intensity on T2W lesion with low signal striations, seen arising from the posterior body and ramus of the mandible. The lesion shows diffusion restriction and heterogeneous enhancement on post-contrast images.
 //! The lesion extends from the anterior to the posterior border of the ramus of the mandible with thinning of the ramus of the mandible.  The lesion is infiltrating the left masseter muscle laterally and medial pterygoid muscle medially. The left parotid gland is seen compressed along the inferolateral margin of the lesion and is medially displacing the left parapharyngeal space towards the midline without infiltrating it.
 //! Ultrasound-guided core needle biopsy with needle tip within the lesion showing reverberation artifact.Histopathology reports suggest a poorly differentiated malignancy.
@@ -89,10 +603,12 @@
 //! let mut baidu = Translate::Baidu.create();
 //! baidu.set_appid("xxxxxxxxxxxx");
 //! baidu.set_secret_key("xxxxxxxxxxxxxx");
-//! baidu.set_from(Language::En);
-//! baidu.set_to(Language::Zh);
-//! baidu.set_domain(DomainType::Medicine);
+//! baidu.set_from(Language::En)?;
+//! baidu.set_to(Language::Zh)?;
+//! baidu.set_domain(DomainType::Medicine)?;
 //! println!("{:?}", baidu.docs(docs));
+//! # Ok(())
+//! # }
 //! ```
 //! 使用垂直行业接口,若使用其他行业，或通用领域，请自行更换接口API
 //!
@@ -116,30 +632,65 @@ macro_rules! map {
 }
 
 pub mod baidu;
+pub mod breaker;
+pub mod cache;
+pub mod capability;
+#[cfg(feature = "cli")]
+pub mod cli;
 pub mod cnkis;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod detect;
+pub mod document;
 pub mod err;
-use err::TransError;
+pub mod fallback;
+pub mod glossary;
+pub mod job;
+pub mod language;
+pub mod limit;
+pub mod meter;
+pub mod middleware;
+pub mod observer;
+pub mod pivot;
+pub mod pool;
+pub mod protect;
+pub mod registry;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(test)]
+mod testing;
+pub mod tm;
+use capability::Capabilities;
+use document::{DocsOptions, Document, Segment, SegmentOutcome};
+use err::{ErrInfoBuildle, TransError};
+pub use language::Language;
+use observer::Observer;
+use std::{fmt, str::FromStr};
 type Result<T> = std::result::Result<T, TransError>;
+
+/// 文档翻译中，翻译失败的句子位置返回的固定字符串
+pub const TRANS_ERR: &str = " ---translation err--- ";
 /// 翻译Trait,所有翻译接口都实现这个Trait
-pub trait Translation<'a> {
+/// 需要`Send`，以便`DocsOptions::workers`并发翻译时把翻译器交给工作线程
+pub trait Translation<'a>: Send {
     /// 设置句子
-    fn set_words(&mut self, words: &'a str);
-    /// 设置翻译源语言
-    fn set_from(&mut self, language: Language);
-    /// 设置翻译结果语言
-    fn set_to(&mut self, language: Language);
+    fn set_words(&mut self, words: &str);
+    /// 设置翻译源语言。接口不支持该语言时返回`TransError::Unsupported`，原设置不变
+    fn set_from(&mut self, language: Language) -> Result<()>;
+    /// 设置翻译结果语言。接口不支持该语言时返回`TransError::Unsupported`，原设置不变
+    fn set_to(&mut self, language: Language) -> Result<()>;
     /// 设置appid
     fn set_appid(&mut self, appid: &'a str);
     /// 设置appid
     fn set_secret_key(&mut self, secret_key: &'a str);
-    /// 设置翻译领域
-    fn set_domain(&mut self, domain: DomainType);
+    /// 设置翻译领域。接口不支持该领域时返回`TransError::Unsupported`，原设置不变
+    fn set_domain(&mut self, domain: DomainType) -> Result<()>;
     /// 设置单句最大长度
     fn set_max_length(&mut self, l: usize);
     /// 获取最大支持长度
-    fn max_length(&mut self) -> usize;
+    fn max_length(&self) -> usize;
     /// 获取传入的句子
-    fn get_words(&self) -> &'a str;
+    fn get_words(&self) -> &str;
     /// 获取源的语言类型
     fn from(&self) -> Language;
     /// 获取目标的语言类型
@@ -150,59 +701,105 @@ pub trait Translation<'a> {
     fn secret_key(&self) -> &'a str;
     /// 领域
     fn domain(&self) -> DomainType;
+    /// 翻译接口名称，用于缓存、日志等区分不同的接口
+    fn provider(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+    /// 上一次`trans()`实际使用的翻译接口。组合多个接口的翻译器(如`FallbackTranslator`)返回产生译文的接口
+    fn served_by(&self) -> &'static str {
+        self.provider()
+    }
+    /// 接口支持的语言对、领域、最大长度等。默认不限语言和领域
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::unrestricted(self.max_length())
+    }
     /// 创建一个翻译器
     fn build(&self) -> Box<dyn Translation<'a> + 'a>;
+    /// 创建一个配置相同的翻译器(appid、秘钥、语言、领域、最大长度)，用于并发翻译
+    fn fork(&self) -> Box<dyn Translation<'a> + 'a> {
+        let mut t = self.build();
+        t.set_appid(self.appid());
+        t.set_secret_key(self.secret_key());
+        // 设置来自同类翻译器，不会被拒绝
+        let _ = t.set_from(self.from());
+        let _ = t.set_to(self.to());
+        let _ = t.set_domain(self.domain());
+        t.set_max_length(self.max_length());
+        t
+    }
     /// 开始调用翻译API 翻译
     fn trans(&mut self) -> Result<String>;
     /// 文档翻译,使用简单的标点【'!', '.', '?', ';', '？', '。', '；', '！'】切分
     /// 结果生成一个Vec<String>
     /// 未检测max_len,如果分割之后，还有超过max_len的情况，将返回固定的" ---translation err--- "
     /// 所有的错误情况，都在对应句子位置 返回 " ---translation err--- "
-    /// 若有更精确的需求，请自行实现，或提issue
+    /// 需要具体错误信息时使用`docs_detailed`，需要其他失败处理方式时使用`docs_with`
     fn docs(&mut self, docs: &'a str) -> Vec<String> {
-        let mut res: Vec<String> = Vec::default();
-        for x in docs.split_inclusive(&['!', '.', '?', ';', '？', '。', '；', '！'][..]) {
-            self.set_words(x);
-            match self.trans() {
-                Ok(r) => res.push(r),
-                Err(_) => res.push(" ---translation err--- ".to_owned()),
+        self.docs_with(docs, &DocsOptions::default())
+            .unwrap_or_default()
+    }
+    /// 文档翻译，切分方式同`docs`。返回每一句的原文、译文或错误、调用次数
+    /// `ErrorPolicy::FailFast`时，遇到失败的句子即停止，最后一个结果为该错误
+    fn docs_detailed(&mut self, docs: &'a str, options: &DocsOptions) -> Vec<SegmentOutcome<'a>> {
+        document::run(self, &document::split(docs), options)
+    }
+    /// 同`docs_detailed`，翻译过程中的开始、完成、失败、重试、结束等事件交给`observer`
+    /// 可用于显示进度条、实时推送已完成的句子
+    fn docs_observed(
+        &mut self,
+        docs: &'a str,
+        options: &DocsOptions,
+        observer: &mut dyn Observer,
+    ) -> Vec<SegmentOutcome<'a>> {
+        document::run_observed(self, &document::split(docs), options, observer)
+    }
+    /// 文档翻译，切分方式同`docs`。失败的句子按`options.policy`处理
+    fn docs_with(&mut self, docs: &'a str, options: &DocsOptions) -> Result<Vec<String>> {
+        let mut res = Vec::default();
+        for outcome in self.docs_detailed(docs, options) {
+            if let Some(r) = options.policy.resolve(outcome)? {
+                res.push(r);
             }
         }
-        res
+        Ok(res)
+    }
+    /// 保留排版的文档翻译。段落、换行、空行、缩进、列表符号原样保留，只翻译其中的句子
+    /// 结果包含对齐的原文/译文句子，`Document::text()`按原排版重建译文
+    /// 翻译失败的句子，译文为`TRANS_ERR`
+    fn translate_document(&mut self, docs: &'a str) -> Document<'a> {
+        let (skeleton, sources) = document::parse(docs);
+        let segments = document::run(self, &sources, &DocsOptions::default())
+            .into_iter()
+            .map(|outcome| Segment {
+                source: outcome.source,
+                translation: outcome.result.unwrap_or_else(|_| TRANS_ERR.to_owned()),
+            })
+            .collect();
+        Document { skeleton, segments }
+    }
+    /// 保留排版的文档翻译。失败的句子按`options.policy`处理，跳过的句子译文为空
+    fn translate_document_with(
+        &mut self,
+        docs: &'a str,
+        options: &DocsOptions,
+    ) -> Result<Document<'a>> {
+        let (skeleton, sources) = document::parse(docs);
+        let mut segments = Vec::with_capacity(sources.len());
+        for outcome in document::run(self, &sources, options) {
+            let source = outcome.source;
+            segments.push(Segment {
+                source,
+                translation: options.policy.resolve(outcome)?.unwrap_or_default(),
+            });
+        }
+        Ok(Document { skeleton, segments })
     }
 }
 
 // enum Punctuation {}
 
-/// 语言类型
-#[derive(Debug, Clone, Copy)]
-pub enum Language {
-    Zh,   //中文
-    En,   //英语
-    Jp,   //日语
-    Kor,  //韩语
-    Th,   //泰语
-    Vie,  //越南语
-    Ru,   //俄语
-    Auto, //自动
-}
-/// 将语言类型，翻译为字符串。百度api使用
-impl From<Language> for &str {
-    fn from(value: Language) -> Self {
-        match value {
-            Language::Zh => "zh",
-            Language::En => "en",
-            Language::Jp => "jp",
-            Language::Kor => "kor",
-            Language::Th => "th",
-            Language::Vie => "vie",
-            Language::Ru => "ru",
-            Language::Auto => "auto",
-        }
-    }
-}
 /// 翻译领域模型
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DomainType {
     /// 电子科技领域
     Electronics,
@@ -216,9 +813,36 @@ pub enum DomainType {
     Novel,
     /// 通用领域 || 无区分领域 传值 百度垂直传递此值 默认electronics
     General,
+    /// 接口自定义的领域标识，原样传给接口，如Baidu的`law`、`news`。
+    /// 接口是否支持见`Capabilities::domains`，不支持时`set_domain`返回`TransError::Unsupported`
+    Custom(String),
+}
+impl DomainType {
+    /// 领域名称。`Custom`为其标识，不含`custom:`前缀
+    pub fn name(&self) -> &str {
+        match self {
+            DomainType::Electronics => "electronics",
+            DomainType::Finance => "finance",
+            DomainType::Mechanics => "mechanics",
+            DomainType::Medicine => "medicine",
+            DomainType::Novel => "novel",
+            DomainType::General => "general",
+            DomainType::Custom(id) => id,
+        }
+    }
+    /// 按`Translation::provider()`取接口使用的领域标识，接口没有对应领域时为`None`。
+    /// `Custom`原样使用
+    pub fn code(&self, provider: &str) -> Option<&str> {
+        match (provider, self) {
+            (_, DomainType::Custom(id)) => Some(id),
+            ("Baidu", _) => Some(self.into()),
+            _ => None,
+        }
+    }
 }
-impl From<DomainType> for &str {
-    fn from(value: DomainType) -> Self {
+/// 将领域类型，翻译为字符串。百度api使用
+impl<'a> From<&'a DomainType> for &'a str {
+    fn from(value: &'a DomainType) -> Self {
         match value {
             DomainType::Electronics => "electronics",
             DomainType::Finance => "finance",
@@ -226,14 +850,50 @@ impl From<DomainType> for &str {
             DomainType::Medicine => "medicine",
             DomainType::Novel => "novel",
             DomainType::General => "electronics",
+            DomainType::Custom(id) => id,
+        }
+    }
+}
+/// 领域名称，`Custom`为`custom:<标识>`，`FromStr`可解析回来
+impl fmt::Display for DomainType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainType::Custom(id) => write!(f, "{}{}", CUSTOM_DOMAIN, id),
+            _ => f.write_str(self.name()),
         }
     }
 }
+/// `DomainType::Custom`的文本前缀
+const CUSTOM_DOMAIN: &str = "custom:";
+/// 解析领域名称，不区分大小写: `general` `electronics` `finance` `mechanics` `medicine` `novel`。
+/// `custom:<标识>`解析为`DomainType::Custom`，标识保留原样；其他名称返回`TransError::ConfigError`
+impl FromStr for DomainType {
+    type Err = TransError;
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim();
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "general" => DomainType::General,
+            "electronics" => DomainType::Electronics,
+            "finance" => DomainType::Finance,
+            "mechanics" => DomainType::Mechanics,
+            "medicine" => DomainType::Medicine,
+            "novel" => DomainType::Novel,
+            lower if lower.starts_with(CUSTOM_DOMAIN) => match name[CUSTOM_DOMAIN.len()..].trim() {
+                "" => return Err(parse_err("DomainType", "领域标识为空", s)),
+                id => DomainType::Custom(id.to_owned()),
+            },
+            _ => return Err(parse_err("DomainType", "不支持的领域", s)),
+        })
+    }
+}
 
 /// 入口Enum。将使用此enum切换特定的API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Translate {
     Cnki,
     Baidu,
+    /// 由`registry::register`注册的接口
+    Registered(&'static str),
     // Xunfei,
     // Youdao,
     // Deepl,
@@ -241,17 +901,163 @@ pub enum Translate {
     // Tencent,
 }
 impl Translate {
-    pub fn create(&self) -> Box<dyn Translation + '_> {
-        match self {
+    /// # Panics
+    /// `Translate::Registered`的名称未注册时panic。由`FromStr`解析得到的名称都已注册，
+    /// 直接构造`Registered`时使用`try_create`
+    pub fn create(&self) -> Box<dyn Translation<'_> + '_> {
+        self.try_create().unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// 同`create`，`Translate::Registered`的名称未注册时返回`TransError::ConfigError`
+    /// ```
+    /// # use translation_api::Translate;
+    /// assert!(Translate::Registered("unknown").try_create().is_err());
+    /// ```
+    pub fn try_create<'a>(&self) -> Result<Box<dyn Translation<'a> + 'a>> {
+        Ok(match self {
             Translate::Cnki => Box::new(cnkis::CNKI::default_new()),
             Translate::Baidu => Box::new(baidu::Baidu::new()),
+            Translate::Registered(name) => registry::registered(name)?.create(None),
+        })
+    }
+    /// 接口名称，`FromStr`可解析回来
+    pub fn name(&self) -> &'static str {
+        match self {
+            Translate::Cnki => "cnki",
+            Translate::Baidu => "baidu",
+            Translate::Registered(name) => name,
         }
     }
+    /// 按配置创建翻译器: 接口、凭据、地址、语言、领域、最大长度，设置了`qps`时加上频率限制
+    /// ```no_run
+    /// # use translation_api::{config::Config, Translate};
+    /// # fn main() -> Result<(), translation_api::err::TransError> {
+    /// let config = Config::load("translation.toml")?;
+    /// let profile = config.profile("baidu-medical")?;
+    /// let mut t = Translate::from_config(&profile)?;
+    /// t.set_words("Hello world.");
+    /// println!("{}", t.trans()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// 接口不支持配置中的语言或领域时返回`TransError::Unsupported`
+    #[cfg(feature = "config")]
+    pub fn from_config(profile: &config::Profile) -> Result<Box<dyn Translation<'_> + '_>> {
+        let mut t: Box<dyn Translation<'_> + '_> = match (profile.provider, &profile.endpoint) {
+            (Translate::Cnki, Some(endpoint)) => {
+                let mut cnki = cnkis::CNKI::default_new();
+                cnki.set_endpoint(endpoint);
+                Box::new(cnki)
+            }
+            (Translate::Baidu, Some(endpoint)) => {
+                let mut baidu = baidu::Baidu::new();
+                baidu.set_endpoint(endpoint);
+                Box::new(baidu)
+            }
+            (Translate::Registered(name), endpoint) => {
+                registry::registered(name)?.create(endpoint.as_deref())
+            }
+            (_, None) => profile.provider.try_create()?,
+        };
+        if let Some(appid) = &profile.appid {
+            t.set_appid(appid);
+        }
+        if let Some(secret_key) = &profile.secret_key {
+            t.set_secret_key(secret_key);
+        }
+        if let Some(from) = profile.from {
+            t.set_from(from)?;
+        }
+        if let Some(to) = profile.to {
+            t.set_to(to)?;
+        }
+        if let Some(domain) = &profile.domain {
+            t.set_domain(domain.clone())?;
+        }
+        if let Some(max_len) = profile.max_len {
+            t.set_max_length(max_len);
+        }
+        Ok(match &profile.rate_limit {
+            Some(limit) => middleware::ProviderBuilder::new(t)
+                .with(limit.clone())
+                .build(),
+            None => t,
+        })
+    }
+}
+/// 解析接口名称，不区分大小写: `cnki` `baidu`，以及`registry::register`注册的名称
+impl FromStr for Translate {
+    type Err = TransError;
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "cnki" => Translate::Cnki,
+            "baidu" => Translate::Baidu,
+            _ => match registry::lookup(s) {
+                Some(name) => Translate::Registered(name),
+                None => return Err(parse_err("Translate", "不支持的接口", s)),
+            },
+        })
+    }
+}
+
+fn parse_err(model: &str, msg: &str, s: &str) -> TransError {
+    TransError::ConfigError(
+        ErrInfoBuildle::new()
+            .model(model)
+            .method("from_str()")
+            .original(format!("{}: {}", msg, s))
+            .build(),
+    )
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
+
+    #[test]
+    fn domain_from_str() {
+        assert_eq!(
+            " Medicine ".parse::<DomainType>().unwrap(),
+            DomainType::Medicine
+        );
+        assert_eq!(
+            "Custom: law".parse::<DomainType>().unwrap(),
+            DomainType::Custom("law".to_owned())
+        );
+        for s in ["law", "medical", "", "custom:", "custom:  "] {
+            assert!(
+                matches!(s.parse::<DomainType>(), Err(TransError::ConfigError(_))),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn domain_round_trip() {
+        for d in [
+            DomainType::General,
+            DomainType::Novel,
+            DomainType::Custom("senimed".to_owned()),
+        ] {
+            assert_eq!(d.to_string().parse::<DomainType>().unwrap(), d);
+        }
+        let law = DomainType::Custom("law".to_owned());
+        assert_eq!(law.to_string(), "custom:law");
+        assert_eq!(law.name(), "law");
+        assert_eq!(law.code("Baidu"), Some("law"));
+    }
+
+    #[test]
+    fn create_unregistered() {
+        let unknown = Translate::Registered("not-registered");
+        assert!(matches!(
+            unknown.try_create().map(|t| t.provider()),
+            Err(TransError::ConfigError(_))
+        ));
+        assert!(std::panic::catch_unwind(|| unknown.create().provider()).is_err());
+        assert_eq!(Translate::Cnki.try_create().unwrap().provider(), "CNKI");
+    }
+
     // #[test]
     // fn it_works_CNKI() {
     //     let words = "A 55-year-old man presented for preoperative assessment following diagnosis of a dysplastic duodenal polyp on a background of familial adenomatous polyposis (FAP). The patient had prior surgical history of subtotal colectomy with ileorectal anastomosis with formation of ileo-anal pouch in 1981 with subsequent stoma reversal in 2000 (more than 20 years earlier).";
@@ -281,8 +1087,8 @@ mod tests {
     //         baidu.set_appid("xxxxxxxxxxxx");
     //         baidu.set_secret_key("xxxxxxxxxxxx");
     //         baidu.set_from(Language::Auto);
-    //         baidu.set_to(Language::Zh);
-    //         baidu.set_domain(DomainType::Medicine);
+    //         baidu.set_to(Language::Zh)?;
+    //         baidu.set_domain(DomainType::Medicine)?;
     //         baidu.set_words(words);
     //         // // 此处可使用环境变量一类的
     //         match baidu.trans() {
@@ -314,9 +1120,9 @@ mod tests {
     //         let mut baidu = Translate::Baidu.create();
     //         baidu.set_appid("xxxxxxxxxxxx");
     //         baidu.set_secret_key("xxxxxxxxxxxxxx");
-    //         baidu.set_from(Language::En);
-    //         baidu.set_to(Language::Zh);
-    //         baidu.set_domain(DomainType::Medicine);
+    //         baidu.set_from(Language::En)?;
+    //         baidu.set_to(Language::Zh)?;
+    //         baidu.set_domain(DomainType::Medicine)?;
     //         println!("{:?}", baidu.docs(docs));
     //     }
 }
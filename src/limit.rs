@@ -0,0 +1,98 @@
+//! 请求频率限制
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    err::{ErrInfoBuildle, TransError},
+    Result,
+};
+
+/// 请求频率限制。克隆后共享同一个计时，可在多个翻译器、多个线程间共用
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    interval: Duration,
+    next: Arc<Mutex<Instant>>,
+}
+
+impl RateLimit {
+    /// 每秒最多`qps`次请求。百度标准版QPS为1，高级版为10
+    /// `qps`不是正的有限数或过小(间隔超出`Duration`范围)时返回`TransError::ConfigError`
+    pub fn per_second(qps: f64) -> Result<Self> {
+        if !(qps > 0.0 && qps.is_finite()) {
+            return Err(qps_err(qps));
+        }
+        Duration::try_from_secs_f64(1.0 / qps)
+            .map(Self::with_interval)
+            .map_err(|_| qps_err(qps))
+    }
+    /// 两次请求之间至少间隔`interval`
+    pub fn with_interval(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+    /// 两次请求的最小间隔
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    /// 阻塞，直到允许发出下一次请求
+    pub fn acquire(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let at = (*next).max(now);
+            *next = at + self.interval;
+            at - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+fn qps_err(qps: f64) -> TransError {
+    TransError::ConfigError(
+        ErrInfoBuildle::new()
+            .model("RateLimit")
+            .method("per_second()")
+            .original(format!("qps必须为正数: {}", qps))
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_second_interval() {
+        let limit = RateLimit::per_second(4.0).unwrap();
+        assert_eq!(limit.interval(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn per_second_rejects_invalid_qps() {
+        for qps in [0.0, -1.0, f64::NAN, f64::INFINITY, f64::MIN_POSITIVE] {
+            assert!(
+                matches!(RateLimit::per_second(qps), Err(TransError::ConfigError(_))),
+                "{}",
+                qps
+            );
+        }
+    }
+
+    #[test]
+    fn clones_share_schedule() {
+        let limit = RateLimit::with_interval(Duration::from_millis(20));
+        let other = limit.clone();
+        let start = Instant::now();
+        limit.acquire();
+        other.acquire();
+        limit.acquire();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
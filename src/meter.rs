@@ -0,0 +1,436 @@
+//! 用量统计与预算
+//!
+//! 按接口、账号、自然月(UTC)统计计费字符数并保存到JSON文件，按价格表估算费用，
+//! 达到设定的上限后拒绝翻译，返回`TransError::BudgetExceeded`。
+//! 统计文件只供一个进程使用: 每次保存都用内存中的统计整体覆盖文件，多个进程同时写同一个文件会丢失其他进程的用量
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 接口价格。免费额度按账号每月计算
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    /// 每月免费字符数
+    pub free_chars: u64,
+    /// 超出免费额度后，每百万字符的价格(元)
+    pub per_million: f64,
+}
+
+impl Price {
+    /// Baidu垂直领域翻译: 每月50万免费字符，超出部分49元/百万字符
+    pub const BAIDU: Price = Price {
+        free_chars: 500_000,
+        per_million: 49.0,
+    };
+    /// 某个账号一个月用了`chars`个字符的费用
+    pub fn cost(&self, chars: u64) -> f64 {
+        chars.saturating_sub(self.free_chars) as f64 * self.per_million / 1_000_000.0
+    }
+}
+
+/// 每个接口每月的用量上限
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget {
+    /// 所有账号合计的字符数
+    Chars(u64),
+    /// 所有账号合计的估算费用(元)
+    Cost(f64),
+}
+
+/// 一个接口的一个账号一个月的用量
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// `2023-05`
+    pub month: String,
+    pub provider: String,
+    /// 账号(appid)，无需账号的接口为空
+    pub credential: String,
+    /// 成功的调用次数
+    pub requests: u64,
+    /// 计费字符数
+    pub chars: u64,
+}
+
+/// 用量统计
+#[derive(Debug, Clone)]
+pub struct UsageMeter {
+    path: Option<PathBuf>,
+    records: Vec<UsageRecord>,
+    prices: HashMap<String, Price>,
+    budgets: HashMap<String, Budget>,
+}
+
+impl Default for UsageMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UsageMeter {
+    /// 只在内存中统计。价格表默认包含`Price::BAIDU`
+    pub fn new() -> Self {
+        UsageMeter {
+            path: None,
+            records: Vec::new(),
+            prices: HashMap::from([("Baidu".to_owned(), Price::BAIDU)]),
+            budgets: HashMap::default(),
+        }
+    }
+    /// 从`path`读取已有的用量，之后每次记录都写回该文件。文件不存在时自动创建。
+    /// 同一个文件同一时间只能由一个`UsageMeter`使用，并发翻译时用`fork()`共用同一个`MeteredTranslator`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let records = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| meter_err("open()", e.to_string(), &path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(meter_err("open()", e.to_string(), &path)),
+        };
+        Ok(UsageMeter {
+            path: Some(path),
+            records,
+            ..Self::new()
+        })
+    }
+    /// 设置接口的价格，`provider`同`Translation::provider()`
+    pub fn price(mut self, provider: &str, price: Price) -> Self {
+        self.prices.insert(provider.to_owned(), price);
+        self
+    }
+    /// 设置接口每月的用量上限
+    pub fn budget(mut self, provider: &str, budget: Budget) -> Self {
+        self.budgets.insert(provider.to_owned(), budget);
+        self
+    }
+    pub fn records(&self) -> &[UsageRecord] {
+        &self.records
+    }
+    /// 接口在`month`(如`2023-05`)所有账号合计的字符数
+    pub fn chars(&self, provider: &str, month: &str) -> u64 {
+        self.of(provider, month).map(|r| r.chars).sum()
+    }
+    /// 接口在`month`所有账号合计的估算费用，未设置价格的接口为0
+    pub fn cost(&self, provider: &str, month: &str) -> f64 {
+        let Some(price) = self.prices.get(provider) else {
+            return 0.0;
+        };
+        self.of(provider, month).map(|r| price.cost(r.chars)).sum()
+    }
+    /// 本月再翻译`chars`个字符是否会超过上限
+    pub fn check(&self, provider: &str, credential: &str, chars: u64) -> Result<()> {
+        let Some(budget) = self.budgets.get(provider) else {
+            return Ok(());
+        };
+        let month = current_month();
+        let exceeded = match *budget {
+            Budget::Chars(cap) => self.chars(provider, &month) + chars > cap,
+            Budget::Cost(cap) => {
+                let used = self
+                    .of(provider, &month)
+                    .find(|r| r.credential == credential)
+                    .map_or(0, |r| r.chars);
+                let price = self.prices.get(provider);
+                let extra = price.map_or(0.0, |p| p.cost(used + chars) - p.cost(used));
+                self.cost(provider, &month) + extra > cap
+            }
+        };
+        if exceeded {
+            return Err(TransError::BudgetExceeded(
+                ErrInfoBuildle::new()
+                    .model("UsageMeter")
+                    .method("check()")
+                    .original(format!("{} 本月用量已达上限 {:?}", provider, budget))
+                    .data(format!(
+                        "{}: {} chars, {:.2}",
+                        month,
+                        self.chars(provider, &month),
+                        self.cost(provider, &month)
+                    ))
+                    .build(),
+            ));
+        }
+        Ok(())
+    }
+    /// 记录一次成功的调用，设置了文件时立即保存
+    pub fn record(&mut self, provider: &str, credential: &str, chars: u64) -> Result<()> {
+        let month = current_month();
+        match self
+            .records
+            .iter_mut()
+            .find(|r| r.month == month && r.provider == provider && r.credential == credential)
+        {
+            Some(r) => {
+                r.requests += 1;
+                r.chars += chars;
+            }
+            None => self.records.push(UsageRecord {
+                month,
+                provider: provider.to_owned(),
+                credential: credential.to_owned(),
+                requests: 1,
+                chars,
+            }),
+        }
+        self.save()
+    }
+    /// 写回文件。先写临时文件再改名，写入中断不会损坏已有的统计。
+    /// 不读取文件中其他进程写入的用量，见`UsageMeter::open`
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&self.records)
+            .map_err(|e| meter_err("save()", e.to_string(), path))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| meter_err("save()", e.to_string(), path))
+    }
+    fn of<'m>(
+        &'m self,
+        provider: &'m str,
+        month: &'m str,
+    ) -> impl Iterator<Item = &'m UsageRecord> {
+        self.records
+            .iter()
+            .filter(move |r| r.provider == provider && r.month == month)
+    }
+}
+
+/// 统计用量的翻译器
+/// `build()`/`fork()`出的翻译器与原翻译器共用同一个`UsageMeter`。
+/// 与`FallbackTranslator`一起使用时，应分别包装每个接口
+pub struct MeteredTranslator<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    meter: Arc<Mutex<UsageMeter>>,
+    save_error: Option<TransError>,
+}
+
+impl<'a> MeteredTranslator<'a> {
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>, meter: UsageMeter) -> Self {
+        MeteredTranslator {
+            inner,
+            meter: Arc::new(Mutex::new(meter)),
+            save_error: None,
+        }
+    }
+    pub fn meter(&self) -> MutexGuard<'_, UsageMeter> {
+        self.meter.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    /// 上一次`trans()`保存用量时的错误。此时译文照常返回，用量已计入内存，下次保存时一并写入文件
+    pub fn save_error(&self) -> Option<&TransError> {
+        self.save_error.as_ref()
+    }
+}
+
+impl<'a> Translation<'a> for MeteredTranslator<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(MeteredTranslator {
+            inner: self.inner.build(),
+            meter: self.meter.clone(),
+            save_error: None,
+        })
+    }
+
+    /// 成功后按原文的字符数计费。检查预算和记录用量都使用`provider()`和调用前的`appid()`，
+    /// 轮换账号的接口(如`BaiduPool`)各账号的准确用量见其自身的统计
+    fn trans(&mut self) -> Result<String> {
+        self.save_error = None;
+        let chars = self.inner.get_words().chars().count() as u64;
+        let (provider, credential) = (self.inner.provider(), self.inner.appid());
+        self.meter().check(provider, credential, chars)?;
+        let res = self.inner.trans()?;
+        let saved = self.meter().record(provider, credential, chars);
+        self.save_error = saved.err();
+        Ok(res)
+    }
+}
+
+/// 当前自然月(UTC)，如`2023-05`
+pub fn current_month() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400) as i64;
+    // 公历日期换算，见 http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}", year, month)
+}
+
+fn meter_err(method: &str, err: String, path: &Path) -> TransError {
+    TransError::IOError(
+        ErrInfoBuildle::new()
+            .model("UsageMeter")
+            .method(method)
+            .original(err)
+            .data(path.display().to_string())
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_path, Mock};
+
+    #[test]
+    fn price_and_budget() {
+        assert_eq!(Price::BAIDU.cost(400_000), 0.0);
+        assert_eq!(Price::BAIDU.cost(1_500_000), 49.0);
+
+        let mut meter = UsageMeter::new()
+            .price(
+                "Mock",
+                Price {
+                    free_chars: 10,
+                    per_million: 1_000_000.0,
+                },
+            )
+            .budget("Mock", Budget::Cost(5.0));
+        meter.record("Mock", "a", 10).unwrap();
+        // 免费额度内
+        assert!(meter.check("Mock", "a", 0).is_ok());
+        assert!(meter.check("Mock", "a", 5).is_ok());
+        assert!(matches!(
+            meter.check("Mock", "a", 6),
+            Err(TransError::BudgetExceeded(_))
+        ));
+        // 其他账号有自己的免费额度
+        assert!(meter.check("Mock", "b", 15).is_ok());
+
+        let meter = UsageMeter::new().budget("Mock", Budget::Chars(10));
+        assert!(meter.check("Mock", "", 10).is_ok());
+        assert!(meter.check("Mock", "", 11).is_err());
+        assert!(meter.check("Other", "", 11).is_ok());
+    }
+
+    #[test]
+    fn records_and_reopens() {
+        let path = temp_path("meter-usage.json");
+        let meter = UsageMeter::open(&path)
+            .unwrap()
+            .budget("Mock", Budget::Chars(12));
+        let mut t = MeteredTranslator::new(Box::new(Mock::new()), meter);
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "HELLO");
+        t.set_words("fail");
+        assert!(t.trans().is_err());
+        t.set_words("world!");
+        assert_eq!(t.trans().unwrap(), "WORLD!");
+        // 5 + 6 + 4 > 12
+        t.set_words("more");
+        assert!(matches!(t.trans(), Err(TransError::BudgetExceeded(_))));
+        assert!(t.save_error().is_none());
+
+        let meter = UsageMeter::open(&path).unwrap();
+        let month = current_month();
+        assert_eq!(meter.chars("Mock", &month), 11);
+        assert_eq!(meter.records()[0].requests, 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_error_keeps_translation() {
+        // 父目录不存在，保存失败
+        let path = temp_path("meter-missing").join("usage.json");
+        let meter = UsageMeter::open(&path).unwrap();
+        let mut t = MeteredTranslator::new(Box::new(Mock::new()), meter);
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "HELLO");
+        assert!(matches!(t.save_error(), Some(TransError::IOError(_))));
+        assert_eq!(t.meter().chars("Mock", &current_month()), 5);
+    }
+
+    #[test]
+    fn month_format() {
+        let month = current_month();
+        assert_eq!(month.len(), 7);
+        assert!(month.starts_with("20") && month.as_bytes()[4] == b'-');
+    }
+}
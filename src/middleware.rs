@@ -0,0 +1,583 @@
+//! 中间件
+//!
+//! 重试、缓存、统计、限流等通用逻辑以层(`Layer`)的形式叠加在任意翻译接口外:
+//! `ProviderBuilder::new(baidu).with(Retry::new(3)).with(Cache::new(10_000)).with(metrics)`。
+//! 自定义逻辑实现`Middleware`的调用前/调用后钩子，用`ProviderBuilder::hook`加入
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cache::CachedTranslator, capability::Capabilities, err::TransError, limit::RateLimit,
+    DomainType, Language, Result, Translation,
+};
+
+/// 一层包装: 接收内层翻译器，返回包装后的翻译器
+/// 闭包`FnOnce(Box<dyn Translation>) -> Box<dyn Translation>`可直接作为层使用，
+/// 如`|t| Box::new(GlossaryTranslator::new(t, glossary))`
+pub trait Layer<'a> {
+    fn layer(self, inner: Box<dyn Translation<'a> + 'a>) -> Box<dyn Translation<'a> + 'a>;
+}
+
+impl<'a, F> Layer<'a> for F
+where
+    F: FnOnce(Box<dyn Translation<'a> + 'a>) -> Box<dyn Translation<'a> + 'a>,
+{
+    fn layer(self, inner: Box<dyn Translation<'a> + 'a>) -> Box<dyn Translation<'a> + 'a> {
+        self(inner)
+    }
+}
+
+/// 一次翻译请求
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// 原文
+    pub words: String,
+    pub from: Language,
+    pub to: Language,
+    pub domain: DomainType,
+    /// 内层翻译器的`provider()`
+    pub provider: &'static str,
+}
+
+/// 调用前/调用后钩子
+/// `build()`/`fork()`出的翻译器共用同一个中间件，需要`Sync`
+pub trait Middleware: Send + Sync {
+    /// 调用前。可修改请求(原文、语言、领域)；返回`Some`时不再调用内层，直接作为结果
+    fn before(&self, _request: &mut Request) -> Result<Option<String>> {
+        Ok(None)
+    }
+    /// 调用后。可修改或替换结果
+    fn after(&self, _request: &Request, result: Result<String>) -> Result<String> {
+        result
+    }
+    /// 包裹一次调用，`next`调用内层翻译器。默认依次执行`before`、`next`、`after`，
+    /// 需要多次调用内层(如重试)或计时时重写
+    fn call(
+        &self,
+        request: &mut Request,
+        next: &mut dyn FnMut(&Request) -> Result<String>,
+    ) -> Result<String> {
+        if let Some(res) = self.before(request)? {
+            return Ok(res);
+        }
+        let result = next(request);
+        self.after(request, result)
+    }
+}
+
+/// 逐层包装翻译器。后加入的层在外层，最先处理请求
+pub struct ProviderBuilder<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+}
+
+impl<'a> ProviderBuilder<'a> {
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>) -> Self {
+        ProviderBuilder { inner }
+    }
+    /// 加入一层
+    pub fn with(self, layer: impl Layer<'a>) -> Self {
+        ProviderBuilder {
+            inner: layer.layer(self.inner),
+        }
+    }
+    /// 加入一个自定义中间件
+    pub fn hook(self, middleware: impl Middleware + 'static) -> Self {
+        self.with(Hooked::layer(middleware))
+    }
+    pub fn build(self) -> Box<dyn Translation<'a> + 'a> {
+        self.inner
+    }
+}
+
+/// 由中间件包装的翻译器
+pub struct Hooked<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    middleware: Arc<dyn Middleware>,
+}
+
+impl<'a> Hooked<'a> {
+    pub fn new(
+        inner: Box<dyn Translation<'a> + 'a>,
+        middleware: impl Middleware + 'static,
+    ) -> Self {
+        Hooked {
+            inner,
+            middleware: Arc::new(middleware),
+        }
+    }
+    fn layer(
+        middleware: impl Middleware + 'static,
+    ) -> impl FnOnce(Box<dyn Translation<'a> + 'a>) -> Box<dyn Translation<'a> + 'a> {
+        move |inner| Box::new(Hooked::new(inner, middleware))
+    }
+}
+
+impl<'a> Translation<'a> for Hooked<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(Hooked {
+            inner: self.inner.build(),
+            middleware: self.middleware.clone(),
+        })
+    }
+
+    /// 中间件对请求的修改只作用于本次调用，调用后恢复原设置。
+    /// 修改后的语言、领域内层不支持时返回`TransError::Unsupported`
+    fn trans(&mut self) -> Result<String> {
+        let original = Request {
+            words: self.inner.get_words().to_owned(),
+            from: self.inner.from(),
+            to: self.inner.to(),
+            domain: self.inner.domain(),
+            provider: self.inner.provider(),
+        };
+        let mut request = original.clone();
+        let inner = &mut self.inner;
+        let res = self.middleware.call(&mut request, &mut |request| {
+            inner.set_words(&request.words);
+            inner.set_from(request.from)?;
+            inner.set_to(request.to)?;
+            inner.set_domain(request.domain.clone())?;
+            inner.trans()
+        });
+        // 原设置是内层接受过的，不会被拒绝
+        self.inner.set_words(&original.words);
+        let _ = self.inner.set_from(original.from);
+        let _ = self.inner.set_to(original.to);
+        let _ = self.inner.set_domain(original.domain);
+        res
+    }
+}
+
+/// 失败后重试。只重试接口本身的失败，熔断和超出预算不重试
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    retries: usize,
+    backoff: Duration,
+}
+
+impl Retry {
+    /// 最多重试`retries`次
+    pub fn new(retries: usize) -> Self {
+        Retry {
+            retries,
+            backoff: Duration::ZERO,
+        }
+    }
+    /// 第n次重试前等待`backoff * 2^(n-1)`
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+impl Middleware for Retry {
+    fn call(
+        &self,
+        request: &mut Request,
+        next: &mut dyn FnMut(&Request) -> Result<String>,
+    ) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            match next(request) {
+                Err(e) if attempt < self.retries && retryable(&e) => {
+                    let wait = self.backoff * 2u32.saturating_pow(attempt as u32);
+                    if !wait.is_zero() {
+                        thread::sleep(wait);
+                    }
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+fn retryable(e: &TransError) -> bool {
+    !matches!(
+        e,
+        TransError::CircuitOpen(_) | TransError::BudgetExceeded(_)
+    ) && e.is_provider_failure()
+}
+
+impl Middleware for RateLimit {
+    fn before(&self, _request: &mut Request) -> Result<Option<String>> {
+        self.acquire();
+        Ok(None)
+    }
+}
+
+/// 调用统计。克隆后共享同一组计数，加入`ProviderBuilder`前先克隆一份用于读取
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    calls: AtomicU64,
+    failures: AtomicU64,
+    chars: AtomicU64,
+    nanos: AtomicU64,
+}
+
+/// `Metrics`的一次读数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetricsSnapshot {
+    /// 调用次数
+    pub calls: u64,
+    /// 失败次数
+    pub failures: u64,
+    /// 成功翻译的原文字符数
+    pub chars: u64,
+    /// 累计耗时
+    pub elapsed: Duration,
+}
+
+impl MetricsSnapshot {
+    /// 平均每次调用的耗时
+    pub fn average(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::ZERO
+        } else {
+            self.elapsed / self.calls as u32
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            calls: self.0.calls.load(Ordering::Relaxed),
+            failures: self.0.failures.load(Ordering::Relaxed),
+            chars: self.0.chars.load(Ordering::Relaxed),
+            elapsed: Duration::from_nanos(self.0.nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Middleware for Metrics {
+    fn call(
+        &self,
+        request: &mut Request,
+        next: &mut dyn FnMut(&Request) -> Result<String>,
+    ) -> Result<String> {
+        let start = Instant::now();
+        let res = next(request);
+        let nanos = start.elapsed().as_nanos() as u64;
+        self.0.calls.fetch_add(1, Ordering::Relaxed);
+        self.0.nanos.fetch_add(nanos, Ordering::Relaxed);
+        match &res {
+            Ok(_) => {
+                let chars = request.words.chars().count() as u64;
+                self.0.chars.fetch_add(chars, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.0.failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        res
+    }
+}
+
+/// 内存缓存层，见`CachedTranslator`
+#[derive(Debug, Clone, Copy)]
+pub struct Cache {
+    capacity: usize,
+    ttl: Option<Duration>,
+}
+
+impl Cache {
+    /// 最多缓存`capacity`条
+    pub fn new(capacity: usize) -> Self {
+        Cache {
+            capacity,
+            ttl: None,
+        }
+    }
+    /// 缓存条目的有效期
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+impl<'a> Layer<'a> for Cache {
+    fn layer(self, inner: Box<dyn Translation<'a> + 'a>) -> Box<dyn Translation<'a> + 'a> {
+        let cached = CachedTranslator::new(inner, self.capacity);
+        match self.ttl {
+            Some(ttl) => Box::new(cached.ttl(ttl)),
+            None => Box::new(cached),
+        }
+    }
+}
+
+macro_rules! middleware_layer {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl<'a> Layer<'a> for $t {
+                fn layer(self, inner: Box<dyn Translation<'a> + 'a>) -> Box<dyn Translation<'a> + 'a> {
+                    Box::new(Hooked::new(inner, self))
+                }
+            }
+        )*
+    };
+}
+
+middleware_layer!(Retry, Metrics, RateLimit);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::testing::Mock;
+
+    /// 记录经过的请求和调用顺序
+    #[derive(Clone)]
+    struct Spy {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Spy {
+        fn before(&self, request: &mut Request) -> Result<Option<String>> {
+            self.log.lock().unwrap().push(format!(
+                "{} before {} {}=>{} {}",
+                self.name, request.words, request.from, request.to, request.domain
+            ));
+            Ok(None)
+        }
+        fn after(&self, _request: &Request, result: Result<String>) -> Result<String> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} after", self.name));
+            result.map(|res| format!("{}+{}", res, self.name))
+        }
+    }
+
+    /// 改写请求的原文、语言和领域
+    struct Rewrite;
+
+    impl Middleware for Rewrite {
+        fn before(&self, request: &mut Request) -> Result<Option<String>> {
+            request.words = format!("<{}>", request.words);
+            request.from = Language::De;
+            request.to = Language::Fra;
+            request.domain = DomainType::Medicine;
+            Ok(None)
+        }
+    }
+
+    fn boxed(mock: Mock) -> Box<dyn Translation<'static>> {
+        Box::new(mock)
+    }
+
+    #[test]
+    fn hooked_restores_settings() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let spy = Spy {
+            name: "spy",
+            log: log.clone(),
+        };
+        let mut t = ProviderBuilder::new(boxed(Mock::new()))
+            .hook(spy)
+            .hook(Rewrite)
+            .build();
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "<HELLO>+spy");
+        assert_eq!(
+            log.lock().unwrap()[0],
+            format!("spy before <hello> de=>fr {}", DomainType::Medicine)
+        );
+        assert_eq!(t.get_words(), "hello");
+        assert_eq!((t.from(), t.to()), (Language::En, Language::Zh));
+        assert_eq!(t.domain(), DomainType::General);
+    }
+
+    #[test]
+    fn hooked_rejects_unsupported_rewrite() {
+        let narrow = Mock::new().with_capabilities(Capabilities {
+            pairs: Some(vec![(Language::En, Language::Zh)]),
+            ..Capabilities::unrestricted(5000)
+        });
+        let mut t = ProviderBuilder::new(boxed(narrow)).hook(Rewrite).build();
+        t.set_words("hello");
+        assert!(matches!(t.trans(), Err(TransError::Unsupported(_))));
+        assert_eq!(t.get_words(), "hello");
+        assert_eq!(t.from(), Language::En);
+    }
+
+    #[test]
+    fn layers_run_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let spy = |name| Spy {
+            name,
+            log: log.clone(),
+        };
+        let mut t = ProviderBuilder::new(boxed(Mock::new()))
+            .hook(spy("first"))
+            .hook(spy("second"))
+            .build();
+        t.set_words("a");
+        // 后加入的层在外层，最先处理请求、最后处理结果
+        assert_eq!(t.trans().unwrap(), "A+first+second");
+        let order: Vec<String> = log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.split(' ').take(2).collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "second before",
+                "first before",
+                "first after",
+                "second after"
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_skips_circuit_and_budget() {
+        let cases = [
+            (TransError::RequestError as fn(_) -> _, 4),
+            (TransError::CircuitOpen, 1),
+            (TransError::BudgetExceeded, 1),
+            (TransError::Unsupported, 1),
+        ];
+        for (kind, expected) in cases {
+            let mock = Mock::new().failing(kind);
+            let calls = mock.calls();
+            let mut t = ProviderBuilder::new(boxed(mock))
+                .with(Retry::new(3))
+                .build();
+            t.set_words("hello");
+            assert!(t.trans().is_err());
+            assert_eq!(calls.load(Ordering::SeqCst), expected);
+        }
+        // 重试成功
+        let mock = Mock::new();
+        let calls = mock.calls();
+        let mut t = ProviderBuilder::new(boxed(mock))
+            .with(Retry::new(3))
+            .build();
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "HELLO");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn metrics_count_calls() {
+        let metrics = Metrics::new();
+        let mut t = ProviderBuilder::new(boxed(Mock::new()))
+            .with(metrics.clone())
+            .build();
+        for words in ["hello", "fail", "你好"] {
+            t.set_words(words);
+            let _ = t.trans();
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            (snapshot.calls, snapshot.failures, snapshot.chars),
+            (3, 1, 7)
+        );
+        assert!(snapshot.average() <= snapshot.elapsed);
+        // fork()出的翻译器共用计数
+        let mut forked = t.fork();
+        forked.set_words("abc");
+        forked.trans().unwrap();
+        assert_eq!(metrics.snapshot().calls, 4);
+    }
+
+    #[test]
+    fn cache_and_closure_layers() {
+        let mock = Mock::new();
+        let calls = mock.calls();
+        let mut t = ProviderBuilder::new(boxed(mock))
+            .with(Cache::new(10))
+            .with(|inner| Box::new(Hooked::new(inner, Rewrite)) as Box<dyn Translation<'static>>)
+            .build();
+        for _ in 0..2 {
+            t.set_words("hello");
+            assert_eq!(t.trans().unwrap(), "<HELLO>");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
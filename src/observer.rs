@@ -0,0 +1,163 @@
+//! 文档翻译过程中的事件回调
+//!
+//! 用于显示进度条、向前端推送已完成的句子等。并发翻译时，事件也都在调用线程上回调
+use std::time::Duration;
+
+use crate::err::TransError;
+
+/// 文档翻译事件。`index`为句子在本次翻译中的下标
+#[derive(Debug)]
+pub enum Event<'e> {
+    /// 开始翻译一个句子
+    SegmentStarted {
+        index: usize,
+        total: usize,
+        source: &'e str,
+    },
+    /// 句子翻译成功
+    SegmentTranslated {
+        index: usize,
+        source: &'e str,
+        translation: &'e str,
+        attempts: usize,
+        elapsed: Duration,
+    },
+    /// 句子翻译失败(已用完重试次数)
+    SegmentFailed {
+        index: usize,
+        source: &'e str,
+        error: &'e TransError,
+        attempts: usize,
+        elapsed: Duration,
+    },
+    /// 句子翻译失败，即将进行第`attempt`次调用
+    Retrying {
+        index: usize,
+        attempt: usize,
+        error: &'e TransError,
+    },
+    /// 全部结束。`FailFast`提前停止时，`translated + failed`小于`total`
+    Finished {
+        total: usize,
+        translated: usize,
+        failed: usize,
+        elapsed: Duration,
+    },
+}
+
+/// 事件观察者。闭包`FnMut(&Event)`可以直接作为观察者使用
+pub trait Observer {
+    fn on_event(&mut self, event: &Event<'_>);
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&Event<'_>),
+{
+    fn on_event(&mut self, event: &Event<'_>) {
+        self(event)
+    }
+}
+
+/// 忽略所有事件
+pub struct Silent;
+
+impl Observer for Silent {
+    fn on_event(&mut self, _event: &Event<'_>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        document::{run_observed, DocsOptions},
+        testing::Mock,
+    };
+
+    /// 按顺序记录事件，去掉耗时等不确定的字段
+    fn record(sources: &[&str], options: &DocsOptions) -> Vec<String> {
+        let mut events = Vec::new();
+        run_observed(&mut Mock::new(), sources, options, &mut |event: &Event<
+            '_,
+        >| {
+            events.push(match event {
+                Event::SegmentStarted { index, total, .. } => {
+                    format!("started {}/{}", index, total)
+                }
+                Event::SegmentTranslated {
+                    index,
+                    translation,
+                    attempts,
+                    ..
+                } => format!("translated {} {} {}", index, translation, attempts),
+                Event::SegmentFailed {
+                    index, attempts, ..
+                } => format!("failed {} {}", index, attempts),
+                Event::Retrying { index, attempt, .. } => {
+                    format!("retrying {} {}", index, attempt)
+                }
+                Event::Finished {
+                    total,
+                    translated,
+                    failed,
+                    ..
+                } => format!("finished {} {} {}", total, translated, failed),
+            })
+        });
+        events
+    }
+
+    #[test]
+    fn sequential_events() {
+        let options = DocsOptions {
+            retries: 1,
+            ..DocsOptions::default()
+        };
+        assert_eq!(
+            record(&["a.", "fail.", "b."], &options),
+            [
+                "started 0/3",
+                "translated 0 A. 1",
+                "started 1/3",
+                "retrying 1 2",
+                "failed 1 2",
+                "started 2/3",
+                "translated 2 B. 1",
+                "finished 3 2 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn parallel_event_counts() {
+        let options = DocsOptions {
+            workers: 3,
+            retries: 1,
+            ..DocsOptions::default()
+        };
+        let events = record(&["a.", "fail.", "b.", "c.", "fail d.", "e."], &options);
+        let count = |prefix: &str| events.iter().filter(|e| e.starts_with(prefix)).count();
+        assert_eq!(count("started"), 6);
+        assert_eq!(count("translated"), 4);
+        assert_eq!(count("retrying"), 2);
+        assert_eq!(count("failed"), 2);
+        assert_eq!(events.last().unwrap(), "finished 6 4 2");
+        // 同一句子的事件以开始为首、以成功或失败为尾
+        for index in 0..6 {
+            let kinds: Vec<&str> = events
+                .iter()
+                .filter_map(|e| {
+                    let mut parts = e.split([' ', '/']);
+                    let kind = parts.next()?;
+                    (parts.next()? == index.to_string()).then_some(kind)
+                })
+                .collect();
+            assert_eq!(kinds.first(), Some(&"started"), "{}", index);
+            assert!(
+                matches!(kinds.last(), Some(&"translated" | &"failed")),
+                "{}",
+                index
+            );
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! 经中间语言转译
+//!
+//! CNKI、Baidu领域翻译只支持中英互译。日文、俄文等先由通用接口译为英文，再由领域接口译为中文。
+//! 两次翻译的结果都保留，便于核对中间译文
+use crate::{
+    capability::Capabilities,
+    document::{self, DocsOptions},
+    DomainType, Language, Result, Translation,
+};
+
+/// 一次转译的中间译文和最终译文
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pivoted {
+    /// 第一个接口译为中间语言的结果
+    pub intermediate: String,
+    /// 第二个接口译为目标语言的结果
+    pub translation: String,
+}
+
+/// 文档转译中单句的结果
+#[derive(Debug)]
+pub struct PivotSegment<'a> {
+    /// 原文句子
+    pub source: &'a str,
+    /// 中间译文。第一个接口失败时为`None`
+    pub intermediate: Option<String>,
+    /// 最终译文或最后一次的错误
+    pub result: Result<String>,
+}
+
+/// 串联两个接口的翻译器: 原文 => 中间语言(`first`) => 目标语言(`second`)
+///
+/// 源语言设置到`first`，目标语言、领域设置到`second`，`first`的领域保持加入前的设置。
+/// 各接口的appid、秘钥不同，需在加入前分别设置，`set_appid`/`set_secret_key`不起作用
+pub struct PivotTranslator<'a> {
+    first: Box<dyn Translation<'a> + 'a>,
+    second: Box<dyn Translation<'a> + 'a>,
+    via: Language,
+    intermediate: Option<String>,
+}
+
+impl<'a> PivotTranslator<'a> {
+    /// `via`为中间语言，设置为`first`的目标语言和`second`的源语言。
+    /// 接口不支持`via`时返回`TransError::Unsupported`
+    pub fn new(
+        mut first: Box<dyn Translation<'a> + 'a>,
+        mut second: Box<dyn Translation<'a> + 'a>,
+        via: Language,
+    ) -> Result<Self> {
+        first.set_to(via)?;
+        second.set_from(via)?;
+        Ok(PivotTranslator {
+            first,
+            second,
+            via,
+            intermediate: None,
+        })
+    }
+    /// 中间语言
+    pub fn via(&self) -> Language {
+        self.via
+    }
+    /// 译为中间语言的接口
+    pub fn first(&self) -> &(dyn Translation<'a> + 'a) {
+        self.first.as_ref()
+    }
+    /// 译为目标语言的接口
+    pub fn second(&self) -> &(dyn Translation<'a> + 'a) {
+        self.second.as_ref()
+    }
+    /// 上一次`trans()`的中间译文。第二个接口失败时仍保留
+    pub fn intermediate(&self) -> Option<&str> {
+        self.intermediate.as_deref()
+    }
+    /// 翻译并同时返回中间译文和最终译文
+    pub fn trans_pivot(&mut self) -> Result<Pivoted> {
+        let translation = self.trans()?;
+        Ok(Pivoted {
+            intermediate: self.intermediate.clone().unwrap_or_default(),
+            translation,
+        })
+    }
+    /// 文档转译，切分方式同`Translation::docs`，每句保留中间译文。
+    /// 为了对应中间译文，逐句顺序翻译，忽略`options.workers`和`options.policy`
+    pub fn docs_pivot(&mut self, docs: &'a str, options: &DocsOptions) -> Vec<PivotSegment<'a>> {
+        let options = DocsOptions {
+            workers: 0,
+            ..options.clone()
+        };
+        let mut res = Vec::new();
+        for source in document::split(docs) {
+            for outcome in document::run(self, &[source], &options) {
+                res.push(PivotSegment {
+                    source: outcome.source,
+                    intermediate: self.intermediate.take(),
+                    result: outcome.result,
+                });
+            }
+        }
+        res
+    }
+}
+
+impl<'a> Translation<'a> for PivotTranslator<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.first.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.first.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.second.set_to(language)
+    }
+
+    fn set_appid(&mut self, _appid: &'a str) {}
+
+    fn set_secret_key(&mut self, _secret_key: &'a str) {}
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.second.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.first.set_max_length(l)
+    }
+
+    /// 原文由`first`翻译，中间译文由`second`翻译，取两者中较小的
+    fn max_length(&self) -> usize {
+        self.first.max_length().min(self.second.max_length())
+    }
+
+    fn get_words(&self) -> &str {
+        self.first.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.first.from()
+    }
+
+    fn to(&self) -> Language {
+        self.second.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        ""
+    }
+
+    fn secret_key(&self) -> &'a str {
+        ""
+    }
+
+    fn domain(&self) -> DomainType {
+        self.second.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        "Pivot"
+    }
+
+    /// 产生最终译文的接口
+    fn served_by(&self) -> &'static str {
+        self.second.served_by()
+    }
+
+    /// `first`中以`via`为目标的源语言，与`second`中以`via`为源的目标语言组合；领域取自`second`
+    fn capabilities(&self) -> Capabilities {
+        let (first, second) = (self.first.capabilities(), self.second.capabilities());
+        let froms: Option<Vec<Language>> = first.pairs.as_ref().map(|pairs| {
+            pairs
+                .iter()
+                .filter(|(_, to)| *to == self.via)
+                .map(|(from, _)| *from)
+                .collect()
+        });
+        let tos: Option<Vec<Language>> = second.pairs.as_ref().map(|pairs| {
+            pairs
+                .iter()
+                .filter(|(from, _)| *from == self.via)
+                .map(|(_, to)| *to)
+                .collect()
+        });
+        let pairs = match (froms, tos) {
+            (None, None) => None,
+            (froms, tos) => {
+                let froms = froms.unwrap_or_else(|| {
+                    std::iter::once(Language::Auto)
+                        .chain(Language::all())
+                        .collect()
+                });
+                let tos = tos.unwrap_or_else(|| Language::all().collect());
+                Some(
+                    froms
+                        .iter()
+                        .flat_map(|from| tos.iter().map(move |to| (*from, *to)))
+                        .collect(),
+                )
+            }
+        };
+        let domains = second.domains.clone();
+        Capabilities {
+            pairs,
+            domains,
+            max_length: self.max_length(),
+            batch: false,
+            ..first.union(second)
+        }
+    }
+
+    /// 两个接口都使用`fork()`复制，保留各自的appid和秘钥
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(PivotTranslator {
+            first: self.first.fork(),
+            second: self.second.fork(),
+            via: self.via,
+            intermediate: None,
+        })
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        self.intermediate = None;
+        let intermediate = self.first.trans()?;
+        self.second.set_words(&intermediate);
+        self.intermediate = Some(intermediate);
+        self.second.trans()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{baidu::Baidu, testing::Mock, Translate};
+
+    /// 与文档中的示例相同的设置
+    #[test]
+    fn documented_setup() {
+        let mut first: Box<dyn Translation<'_>> = Box::new(Mock::new().named("General"));
+        first.set_from(Language::Jp).unwrap();
+        let mut medical = Translate::Baidu.create();
+        medical.set_domain(DomainType::Medicine).unwrap();
+        let mut pivot = PivotTranslator::new(first, medical, Language::En).unwrap();
+        assert_eq!(pivot.from(), Language::Jp);
+        assert_eq!(pivot.to(), Language::Zh);
+        assert!(pivot.set_from(Language::Ru).is_ok());
+        assert!(pivot
+            .capabilities()
+            .supports_pair(Language::Ru, Language::Zh));
+        // Baidu领域翻译不能作为日文的第一个接口
+        assert!(Baidu::new().set_from(Language::Jp).is_err());
+    }
+
+    #[test]
+    fn keeps_intermediate() {
+        let mut pivot = PivotTranslator::new(
+            Box::new(Mock::new().named("First")),
+            Box::new(Mock::new().named("Second")),
+            Language::En,
+        )
+        .unwrap();
+        pivot.set_words("hello");
+        let res = pivot.trans_pivot().unwrap();
+        assert_eq!(res.intermediate, "HELLO");
+        assert_eq!(res.translation, "HELLO");
+        assert_eq!(pivot.served_by(), "Second");
+
+        let segments = pivot.docs_pivot("one. fail two.", &DocsOptions::default());
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].intermediate.as_deref(), Some("ONE."));
+        assert!(segments[1].intermediate.is_none() && segments[1].result.is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_via() {
+        // CNKI只支持中英互译，不能以日文为中间语言
+        let res = PivotTranslator::new(
+            Box::new(Mock::new()),
+            Translate::Cnki.create(),
+            Language::Jp,
+        );
+        assert!(matches!(res, Err(crate::err::TransError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,315 @@
+//! Baidu多账号轮换
+//!
+//! 单个appid有QPS限制和每月免费额度。使用多组appid/秘钥轮流翻译，
+//! 额度用完或认证失败的账号暂停使用一段时间
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    baidu::Baidu,
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 出现这些错误码时暂停使用该账号: 54004 余额不足、58001 译文语言方向不支持、52003 未授权用户
+pub const EJECT_CODES: [&str; 3] = ["54004", "58001", "52003"];
+
+/// 账号选择方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// 依次轮流使用
+    #[default]
+    RoundRobin,
+    /// 使用已翻译字符数最少的账号，相同时使用调用次数少的
+    LeastUsed,
+}
+
+/// 单个账号的使用情况
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub appid: String,
+    /// 调用次数
+    pub requests: u64,
+    /// 成功翻译的字符数(免费额度按字符计算)
+    pub chars: u64,
+    /// 失败次数
+    pub failures: u64,
+    /// 被暂停的次数
+    pub ejections: u64,
+    /// 正处于暂停中时，距恢复使用的时间
+    pub ejected_for: Option<Duration>,
+    /// 最后一次错误
+    pub last_error: Option<String>,
+}
+
+struct Entry {
+    usage: Usage,
+    ejected_until: Option<Instant>,
+}
+
+struct State {
+    entries: Vec<Entry>,
+    next: usize,
+}
+
+impl State {
+    /// 选出一个可用的账号，跳过暂停中的和本次已试过的
+    fn pick(&mut self, strategy: Strategy, tried: &[usize]) -> Option<usize> {
+        let now = Instant::now();
+        let n = self.entries.len();
+        let available = |i: &usize| {
+            !tried.contains(i) && self.entries[*i].ejected_until.is_none_or(|t| t <= now)
+        };
+        let picked = match strategy {
+            Strategy::RoundRobin => (0..n).map(|k| (self.next + k) % n).find(available),
+            Strategy::LeastUsed => (0..n).filter(available).min_by_key(|i| {
+                (
+                    self.entries[*i].usage.chars,
+                    self.entries[*i].usage.requests,
+                )
+            }),
+        }?;
+        self.next = (picked + 1) % n;
+        Some(picked)
+    }
+}
+
+/// 多个Baidu账号轮换使用的翻译器
+/// `build()`/`fork()`出的翻译器与原翻译器共用账号状态，可用于并发翻译
+pub struct BaiduPool<'a> {
+    baidu: Baidu<'a>,
+    credentials: Arc<Vec<(&'a str, &'a str)>>,
+    state: Arc<Mutex<State>>,
+    strategy: Strategy,
+    cooldown: Duration,
+}
+
+impl<'a> BaiduPool<'a> {
+    /// `credentials`为(appid, 秘钥)
+    pub fn new(credentials: Vec<(&'a str, &'a str)>) -> Self {
+        let entries = credentials
+            .iter()
+            .map(|(appid, _)| Entry {
+                usage: Usage {
+                    appid: appid.to_string(),
+                    requests: 0,
+                    chars: 0,
+                    failures: 0,
+                    ejections: 0,
+                    ejected_for: None,
+                    last_error: None,
+                },
+                ejected_until: None,
+            })
+            .collect();
+        BaiduPool {
+            baidu: Baidu::new(),
+            credentials: Arc::new(credentials),
+            state: Arc::new(Mutex::new(State { entries, next: 0 })),
+            strategy: Strategy::default(),
+            cooldown: Duration::from_secs(3600),
+        }
+    }
+    /// 账号选择方式，默认轮流使用
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    /// 接口地址，见`Baidu::set_endpoint`
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.baidu.set_endpoint(endpoint);
+        self
+    }
+    /// 账号出现`EJECT_CODES`中的错误后暂停使用的时间，默认1小时
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+    /// 各账号的使用情况，顺序同创建时的`credentials`
+    pub fn usage(&self) -> Vec<Usage> {
+        let now = Instant::now();
+        self.state()
+            .entries
+            .iter()
+            .map(|e| Usage {
+                ejected_for: e.ejected_until.filter(|t| *t > now).map(|t| t - now),
+                ..e.usage.clone()
+            })
+            .collect()
+    }
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<'a> Translation<'a> for BaiduPool<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.baidu.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.baidu.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.baidu.set_to(language)
+    }
+
+    /// 账号由`BaiduPool::new`提供，此处不起作用
+    fn set_appid(&mut self, _appid: &'a str) {}
+
+    fn set_secret_key(&mut self, _secret_key: &'a str) {}
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.baidu.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.baidu.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.baidu.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.baidu.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.baidu.from()
+    }
+
+    fn to(&self) -> Language {
+        self.baidu.to()
+    }
+
+    /// 上一次使用的账号
+    fn appid(&self) -> &'a str {
+        self.baidu.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.baidu.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.baidu.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.baidu.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.baidu.capabilities()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        let mut baidu = Baidu::new();
+        baidu.set_endpoint(self.baidu.endpoint());
+        Box::new(BaiduPool {
+            baidu,
+            credentials: self.credentials.clone(),
+            state: self.state.clone(),
+            strategy: self.strategy,
+            cooldown: self.cooldown,
+        })
+    }
+
+    /// 账号被暂停时，本句换下一个可用账号重新翻译；所有账号都不可用时返回错误
+    fn trans(&mut self) -> Result<String> {
+        let mut tried = Vec::new();
+        let mut last_err = None;
+        loop {
+            let picked = self.state().pick(self.strategy, &tried);
+            let Some(i) = picked else {
+                return Err(last_err.unwrap_or_else(|| {
+                    TransError::BaiduError(
+                        ErrInfoBuildle::new()
+                            .model("BaiduPool")
+                            .method("trans()")
+                            .original("没有可用的账号".to_owned())
+                            .build(),
+                    )
+                }));
+            };
+            tried.push(i);
+            let (appid, secret_key) = self.credentials[i];
+            self.baidu.set_appid(appid);
+            self.baidu.set_secret_key(secret_key);
+            let res = self.baidu.trans();
+
+            let mut state = self.state();
+            let entry = &mut state.entries[i];
+            entry.usage.requests += 1;
+            match res {
+                Ok(res) => {
+                    entry.usage.chars += self.baidu.get_words().chars().count() as u64;
+                    return Ok(res);
+                }
+                Err(e) => {
+                    entry.usage.failures += 1;
+                    entry.usage.last_error = Some(e.to_string().trim().to_owned());
+                    if !e.code().is_some_and(|c| EJECT_CODES.contains(&c)) {
+                        return Err(e);
+                    }
+                    entry.usage.ejections += 1;
+                    entry.ejected_until = Some(Instant::now() + self.cooldown);
+                    last_err = Some(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(n: usize) -> State {
+        let pool = BaiduPool::new(vec![("a", "1"); n]);
+        Arc::into_inner(pool.state).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn round_robin_skips_ejected_and_tried() {
+        let mut state = state(3);
+        let picks: Vec<_> = (0..4)
+            .map(|_| state.pick(Strategy::RoundRobin, &[]))
+            .collect();
+        assert_eq!(picks, [Some(0), Some(1), Some(2), Some(0)]);
+        state.entries[2].ejected_until = Some(Instant::now() + Duration::from_secs(60));
+        assert_eq!(state.pick(Strategy::RoundRobin, &[1]), Some(0));
+        assert_eq!(state.pick(Strategy::RoundRobin, &[0, 1]), None);
+        // 暂停期已过
+        state.entries[2].ejected_until = Some(Instant::now());
+        assert_eq!(state.pick(Strategy::RoundRobin, &[0, 1]), Some(2));
+    }
+
+    #[test]
+    fn least_used_prefers_fewest_chars() {
+        let mut state = state(3);
+        state.entries[0].usage.chars = 10;
+        state.entries[1].usage.chars = 5;
+        state.entries[2].usage.chars = 5;
+        state.entries[1].usage.requests = 2;
+        assert_eq!(state.pick(Strategy::LeastUsed, &[]), Some(2));
+        assert_eq!(state.pick(Strategy::LeastUsed, &[2]), Some(1));
+    }
+
+    #[test]
+    fn build_keeps_endpoint_and_state() {
+        // 无法连接的地址，请求立即失败
+        let pool = BaiduPool::new(vec![("a", "1"), ("b", "2")]).endpoint("http://127.0.0.1:1/");
+        let mut forked = pool.fork();
+        forked.set_words("hello");
+        assert!(matches!(forked.trans(), Err(TransError::RequestError(_))));
+        let usage = pool.usage();
+        assert_eq!((usage[0].requests, usage[0].failures), (1, 1));
+        assert_eq!(usage[1].requests, 0);
+    }
+}
@@ -0,0 +1,426 @@
+//! 不翻译内容的保护
+//!
+//! 基因/蛋白符号(EWSR1、CD99、NKX 2.2)、序列名(T1W)、剂量、网址等，经接口翻译后常被改写或拆开。
+//! 翻译前把它们替换为占位符，翻译后再换回原文，并报告接口丢失或重复的占位符
+use std::{ops::Range, sync::Arc, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::{
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 内置的保护规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Builtin {
+    /// 网址，如`https://example.com/a?b=1`、`www.example.com`
+    Url,
+    /// 电子邮箱
+    Email,
+    /// 带单位的数字，如`5 mg/kg`、`1.5T`、`37.5°C`、`10-20%`
+    Quantity,
+    /// 含数字的大写符号，如`EWSR1`、`CD99`、`T1W`、`EWSR1-FLI1`、`IL-6`、`NKX 2.2`
+    Symbol,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 4] = [
+        Builtin::Url,
+        Builtin::Email,
+        Builtin::Quantity,
+        Builtin::Symbol,
+    ];
+    fn pattern(self) -> &'static str {
+        match self {
+            Builtin::Url => {
+                r#"(?i)\b(?:(?:https?|ftp)://|www\.)[^\s<>"'()\[\]{}，。；）]*[^\s<>"'()\[\]{}，。；）.,;:!?]"#
+            }
+            Builtin::Email => r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b",
+            Builtin::Quantity => {
+                r"\b\d+(?:[.,]\d+)?(?:\s?[-–~]\s?\d+(?:[.,]\d+)?)?\s?(?:(?:mg/kg|mg/m2|mg/dL|mg/L|mmol/L|µmol/L|μmol/L|mol/L|IU/L|U/L|mg|µg|μg|ug|ng|kg|g|mL|ml|µL|μL|L|mmol|µmol|μmol|mEq|IU|mmHg|cm|mm|µm|μm|nm|min|ms|h|s|Hz|kHz|MHz|Gy|cGy|MBq|Bq|T)\b|°C|%)"
+            }
+            Builtin::Symbol => {
+                r"\b(?:[A-Z]{2,}[0-9]*\s?[0-9]+\.[0-9]+|[A-Z]+[0-9]+[A-Z0-9]*(?:[-/][A-Z0-9]+)*|[A-Z]+-[0-9]+[A-Z]?)\b"
+            }
+        }
+    }
+}
+
+/// 接口对占位符的改动。列出的是被保护的原文
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtectReport {
+    /// 译文中丢失的
+    pub dropped: Vec<String>,
+    /// 译文中出现了不止一次的
+    pub duplicated: Vec<String>,
+}
+
+impl ProtectReport {
+    /// 所有占位符都恰好出现一次
+    pub fn is_clean(&self) -> bool {
+        self.dropped.is_empty() && self.duplicated.is_empty()
+    }
+}
+
+/// 保护规则
+#[derive(Debug, Clone)]
+pub struct Protector {
+    patterns: Vec<Regex>,
+}
+
+impl Default for Protector {
+    /// 启用全部内置规则
+    fn default() -> Self {
+        Builtin::ALL
+            .into_iter()
+            .fold(Self::empty(), |p, b| p.builtin(b))
+    }
+}
+
+impl Protector {
+    /// 启用全部内置规则
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 不含任何规则
+    pub fn empty() -> Self {
+        Protector {
+            patterns: Vec::new(),
+        }
+    }
+    /// 启用一条内置规则
+    pub fn builtin(mut self, builtin: Builtin) -> Self {
+        self.patterns
+            .push(Regex::new(builtin.pattern()).expect("builtin pattern"));
+        self
+    }
+    /// 添加自定义规则(正则表达式)。正则无效时返回`TransError::ConfigError`
+    pub fn pattern(mut self, pattern: &str) -> Result<Self> {
+        let re = Regex::new(pattern).map_err(|e| {
+            TransError::ConfigError(
+                ErrInfoBuildle::new()
+                    .model("Protector")
+                    .method("pattern()")
+                    .original(e.to_string())
+                    .data(pattern.to_owned())
+                    .build(),
+            )
+        })?;
+        self.patterns.push(re);
+        Ok(self)
+    }
+    /// 需要保护的位置。多条规则重叠时，取开始位置靠前的，同一位置取较长的
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found: Vec<Range<usize>> = self
+            .patterns
+            .iter()
+            .flat_map(|p| p.find_iter(text).map(|m| m.range()))
+            .filter(|r| !r.is_empty())
+            .collect();
+        found.sort_by_key(|r| (r.start, std::cmp::Reverse(r.end)));
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(found.len());
+        for r in found {
+            if ranges.last().is_none_or(|last| r.start >= last.end) {
+                ranges.push(r);
+            }
+        }
+        ranges
+    }
+    /// 把需要保护的内容替换为占位符`[@0]`、`[@1]`...，返回替换后的文本和每个占位符对应的原文
+    pub fn protect(&self, text: &str) -> (String, Vec<String>) {
+        let ranges = self.find(text);
+        let tokens = ranges.iter().map(|r| text[r.clone()].to_owned()).collect();
+        (mask(text, ranges, Marker::Token), tokens)
+    }
+    /// 把译文中的占位符换回原文，并报告丢失或重复的占位符
+    pub fn restore(&self, output: &str, tokens: &[String]) -> (String, ProtectReport) {
+        let (restored, counts) = unmask(output, Marker::Token, tokens);
+        let mut report = ProtectReport::default();
+        for (token, count) in tokens.iter().zip(counts) {
+            match count {
+                0 => report.dropped.push(token.clone()),
+                1 => {}
+                _ => report.duplicated.push(token.clone()),
+            }
+        }
+        (restored, report)
+    }
+}
+
+/// 保护不翻译内容的翻译器
+/// `build()`/`fork()`出的翻译器与原翻译器共用同一套规则
+pub struct ProtectedTranslator<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    protector: Arc<Protector>,
+    report: ProtectReport,
+}
+
+impl<'a> ProtectedTranslator<'a> {
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>, protector: Protector) -> Self {
+        ProtectedTranslator {
+            inner,
+            protector: Arc::new(protector),
+            report: ProtectReport::default(),
+        }
+    }
+    pub fn protector(&self) -> &Protector {
+        &self.protector
+    }
+    /// 上一次`trans()`中接口丢失或重复的占位符
+    pub fn report(&self) -> &ProtectReport {
+        &self.report
+    }
+}
+
+impl<'a> Translation<'a> for ProtectedTranslator<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(ProtectedTranslator {
+            inner: self.inner.build(),
+            protector: self.protector.clone(),
+            report: ProtectReport::default(),
+        })
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        self.report = ProtectReport::default();
+        let source = self.inner.get_words().to_owned();
+        let (masked, tokens) = self.protector.protect(&source);
+        if tokens.is_empty() {
+            return self.inner.trans();
+        }
+        self.inner.set_words(&masked);
+        let res = self.inner.trans();
+        self.inner.set_words(&source);
+        let (res, report) = self.protector.restore(&res?, &tokens);
+        self.report = report;
+        Ok(res)
+    }
+}
+
+/// 占位符的标记字符。术语表与保护规则使用不同的标记，两者可以叠加使用
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Marker {
+    /// `[#n]`，术语表
+    Term,
+    /// `[@n]`，保护规则
+    Token,
+}
+
+impl Marker {
+    fn char(self) -> char {
+        match self {
+            Marker::Term => '#',
+            Marker::Token => '@',
+        }
+    }
+    /// 识别译文中的占位符，容忍接口加入的空格和全角字符
+    fn pattern(self) -> &'static Regex {
+        static TERM: OnceLock<Regex> = OnceLock::new();
+        static TOKEN: OnceLock<Regex> = OnceLock::new();
+        let (cell, fullwidth) = match self {
+            Marker::Term => (&TERM, '＃'),
+            Marker::Token => (&TOKEN, '＠'),
+        };
+        cell.get_or_init(|| {
+            Regex::new(&format!(
+                r"[\[［【]\s*[{}{}]\s*(\d+)\s*[\]］】]",
+                self.char(),
+                fullwidth
+            ))
+            .unwrap()
+        })
+    }
+}
+
+/// 把`ranges`处的文本依次替换为占位符
+pub(crate) fn mask(
+    text: &str,
+    ranges: impl IntoIterator<Item = Range<usize>>,
+    marker: Marker,
+) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut last = 0;
+    for (n, range) in ranges.into_iter().enumerate() {
+        masked.push_str(&text[last..range.start]);
+        masked.push_str(&format!("[{}{}]", marker.char(), n));
+        last = range.end;
+    }
+    masked.push_str(&text[last..]);
+    masked
+}
+
+/// 把第`n`个占位符替换为`values[n]`，返回替换后的文本和每个占位符出现的次数。
+/// 超出范围的占位符原样保留
+pub(crate) fn unmask(output: &str, marker: Marker, values: &[String]) -> (String, Vec<usize>) {
+    let mut counts = vec![0; values.len()];
+    let restored = marker
+        .pattern()
+        .replace_all(output, |caps: &regex::Captures<'_>| {
+            match caps[1].parse::<usize>().ok().filter(|n| *n < values.len()) {
+                Some(n) => {
+                    counts[n] += 1;
+                    values[n].clone()
+                }
+                None => caps[0].to_owned(),
+            }
+        })
+        .into_owned();
+    (restored, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Mock;
+
+    fn found<'t>(p: &Protector, text: &'t str) -> Vec<&'t str> {
+        p.find(text).into_iter().map(|r| &text[r]).collect()
+    }
+
+    #[test]
+    fn builtin_rules() {
+        let p = Protector::new();
+        assert_eq!(
+            found(
+                &p,
+                "EWSR1-FLI1 fusion, NKX 2.2 and CD99 on T1W at 1.5T; IL-6 up."
+            ),
+            ["EWSR1-FLI1", "NKX 2.2", "CD99", "T1W", "1.5T", "IL-6"]
+        );
+        assert_eq!(
+            found(
+                &p,
+                "dose 5 mg/kg, 37.5°C, 10-20%, in 2020 we saw 3 patients."
+            ),
+            ["5 mg/kg", "37.5°C", "10-20%"]
+        );
+        assert_eq!(
+            found(&p, "see https://example.com/a?b=1. Mail a.b@x.org."),
+            ["https://example.com/a?b=1", "a.b@x.org"]
+        );
+    }
+
+    #[test]
+    fn overlapping_rules_take_longest() {
+        let p = Protector::empty()
+            .pattern(r"CD\d+")
+            .unwrap()
+            .pattern(r"CD\d+ antigen")
+            .unwrap();
+        assert_eq!(found(&p, "CD99 antigen and CD20"), ["CD99 antigen", "CD20"]);
+    }
+
+    #[test]
+    fn invalid_pattern_is_config_error() {
+        assert!(matches!(
+            Protector::empty().pattern("("),
+            Err(TransError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn protect_and_restore() {
+        let p = Protector::new();
+        let (masked, tokens) = p.protect("CD99 and 5 mg.");
+        assert_eq!(masked, "[@0] and [@1].");
+        assert_eq!(tokens, ["CD99", "5 mg"]);
+
+        let (restored, report) = p.restore("【 ＠0 】和［@1］。", &tokens);
+        assert_eq!(restored, "CD99和5 mg。");
+        assert!(report.is_clean());
+
+        let (restored, report) = p.restore("[@0] [@0] [@7]", &tokens);
+        assert_eq!(restored, "CD99 CD99 [@7]");
+        assert_eq!(report.duplicated, ["CD99"]);
+        assert_eq!(report.dropped, ["5 mg"]);
+    }
+
+    #[test]
+    fn term_and_token_markers_are_independent() {
+        let masked = mask("a b", std::iter::once(0..1), Marker::Term);
+        assert_eq!(masked, "[#0] b");
+        let (restored, counts) = unmask(&masked, Marker::Token, &["x".to_owned()]);
+        assert_eq!((restored.as_str(), counts), ("[#0] b", vec![0]));
+    }
+
+    #[test]
+    fn translator_restores_tokens() {
+        let mut t = ProtectedTranslator::new(Box::new(Mock::new()), Protector::new());
+        t.set_words("positive for cd99 and CD99");
+        assert_eq!(t.trans().unwrap(), "POSITIVE FOR CD99 AND CD99");
+        t.set_words("dose 5 mg daily");
+        assert_eq!(t.trans().unwrap(), "DOSE 5 mg DAILY");
+        assert!(t.report().is_clean());
+        assert_eq!(t.get_words(), "dose 5 mg daily");
+    }
+}
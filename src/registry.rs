@@ -0,0 +1,182 @@
+//! 翻译接口注册表
+//!
+//! 应用可按名称注册自己的`Translation`实现。注册后`Translate::from_str`能解析该名称，
+//! 配置文件中的`provider`、`Translate::from_config`、`FallbackTranslator`等与CNKI、Baidu一样使用
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::{
+    err::{ErrInfoBuildle, TransError},
+    Result, Translate, Translation,
+};
+
+/// 创建翻译器。`endpoint`为配置中的接口地址，未设置时为`None`
+///
+/// 函数`fn(Option<&str>) -> Box<dyn Translation<'_> + '_>`即实现了该trait。
+/// appid、秘钥、语言、领域等由`Translate::from_config`在创建后设置
+pub trait Factory: Send + Sync {
+    fn create<'a>(&self, endpoint: Option<&'a str>) -> Box<dyn Translation<'a> + 'a>;
+}
+
+impl<F> Factory for F
+where
+    F: for<'a> Fn(Option<&'a str>) -> Box<dyn Translation<'a> + 'a> + Send + Sync,
+{
+    fn create<'a>(&self, endpoint: Option<&'a str>) -> Box<dyn Translation<'a> + 'a> {
+        self(endpoint)
+    }
+}
+
+/// 内置接口的名称
+const BUILTIN: [&str; 2] = ["cnki", "baidu"];
+
+struct Entry {
+    name: &'static str,
+    factory: Arc<dyn Factory>,
+}
+
+fn entries() -> &'static RwLock<Vec<Entry>> {
+    static ENTRIES: OnceLock<RwLock<Vec<Entry>>> = OnceLock::new();
+    ENTRIES.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// 注册接口。名称不区分大小写，不能与内置接口或已注册的接口重名
+/// ```
+/// # use translation_api::{registry, Translate, Translation};
+/// # use translation_api::cnkis::CNKI;
+/// // 自己的接口，这里以CNKI代替
+/// fn in_house(endpoint: Option<&str>) -> Box<dyn Translation<'_> + '_> {
+///     let mut t = CNKI::default_new();
+///     if let Some(endpoint) = endpoint {
+///         t.set_endpoint(endpoint);
+///     }
+///     Box::new(t)
+/// }
+/// registry::register("in-house", in_house).unwrap();
+/// assert_eq!("in-house".parse::<Translate>().unwrap(), Translate::Registered("in-house"));
+/// ```
+pub fn register(name: &'static str, factory: impl Factory + 'static) -> Result<()> {
+    let mut entries = entries().write().unwrap_or_else(|e| e.into_inner());
+    let taken = BUILTIN.iter().any(|b| b.eq_ignore_ascii_case(name))
+        || entries.iter().any(|e| e.name.eq_ignore_ascii_case(name));
+    if name.trim().is_empty() || taken {
+        return Err(registry_err("register()", "接口名称为空或已存在", name));
+    }
+    entries.push(Entry {
+        name,
+        factory: Arc::new(factory),
+    });
+    Ok(())
+}
+
+/// 已注册接口的工厂，名称不区分大小写
+pub fn factory(name: &str) -> Option<Arc<dyn Factory>> {
+    find(name, |e| e.factory.clone())
+}
+
+/// 所有接口的名称: 内置接口在前，其余按注册顺序
+pub fn names() -> Vec<&'static str> {
+    let entries = entries().read().unwrap_or_else(|e| e.into_inner());
+    BUILTIN
+        .into_iter()
+        .chain(entries.iter().map(|e| e.name))
+        .collect()
+}
+
+/// 按名称创建翻译器，包括内置接口
+/// ```no_run
+/// # use translation_api::{fallback::FallbackTranslator, registry};
+/// # fn main() -> Result<(), translation_api::err::TransError> {
+/// let translator = FallbackTranslator::new(vec![registry::create("in-house")?, registry::create("cnki")?]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn create(name: &str) -> Result<Box<dyn Translation<'static> + 'static>> {
+    name.parse::<Translate>()?.try_create()
+}
+
+/// 注册时保存的名称(保留注册时的大小写)
+pub(crate) fn lookup(name: &str) -> Option<&'static str> {
+    find(name, |e| e.name)
+}
+
+/// 已注册接口的工厂，未注册时返回`TransError::ConfigError`
+pub(crate) fn registered(name: &str) -> Result<Arc<dyn Factory>> {
+    factory(name).ok_or_else(|| registry_err("factory()", "未注册的接口", name))
+}
+
+fn find<T>(name: &str, f: impl Fn(&Entry) -> T) -> Option<T> {
+    let entries = entries().read().unwrap_or_else(|e| e.into_inner());
+    entries
+        .iter()
+        .find(|e| e.name.eq_ignore_ascii_case(name.trim()))
+        .map(f)
+}
+
+fn registry_err(method: &str, msg: &str, name: &str) -> TransError {
+    TransError::ConfigError(
+        ErrInfoBuildle::new()
+            .model("Registry")
+            .method(method)
+            .original(format!("{}: {}", msg, name))
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Mock;
+
+    fn mock(_endpoint: Option<&str>) -> Box<dyn Translation<'_> + '_> {
+        Box::new(Mock::new().named("Registered"))
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        register("registry-dup", mock).unwrap();
+        assert!(matches!(
+            register("registry-dup", mock),
+            Err(TransError::ConfigError(_))
+        ));
+        assert!(register("REGISTRY-DUP", mock).is_err());
+        assert!(register("", mock).is_err());
+        assert!(register("  ", mock).is_err());
+    }
+
+    #[test]
+    fn rejects_builtin_names() {
+        for name in ["cnki", "baidu", "CNKI", "Baidu"] {
+            assert!(register(name, mock).is_err(), "{}", name);
+        }
+        assert_eq!("Baidu".parse::<Translate>().unwrap(), Translate::Baidu);
+    }
+
+    #[test]
+    fn lookup_ignores_case() {
+        register("Registry-Case", mock).unwrap();
+        assert!(factory("registry-case").is_some());
+        assert!(factory("registry-other").is_none());
+        let parsed: Translate = " REGISTRY-CASE ".parse().unwrap();
+        // 保留注册时的大小写
+        assert_eq!(parsed, Translate::Registered("Registry-Case"));
+        assert!(names().starts_with(&["cnki", "baidu"]));
+        assert!(names().contains(&"Registry-Case"));
+        assert_eq!(create("registry-case").unwrap().provider(), "Registered");
+        assert!(matches!(
+            create("registry-missing"),
+            Err(TransError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn from_str_round_trip() {
+        register("registry-round-trip", mock).unwrap();
+        for t in [
+            Translate::Cnki,
+            Translate::Baidu,
+            Translate::Registered("registry-round-trip"),
+        ] {
+            assert_eq!(t.name().parse::<Translate>().unwrap(), t);
+        }
+    }
+}
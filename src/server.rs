@@ -0,0 +1,650 @@
+//! 翻译HTTP服务
+//!
+//! 凭据保存在服务端，其他语言的服务通过HTTP共用同一个带缓存、限流的翻译接口
+//! ```text
+//! translation-server --config server.toml
+//! curl -d '{"text":"Hello world.","provider":"baidu","to":"zh"}' localhost:8080/translate
+//! ```
+//! 接口:
+//! - `POST /translate` `{"text", "provider"?, "from"?, "to"?, "domain"?}`
+//! - `POST /translate/batch` `{"texts": [..], ..}`，逐条返回译文或错误
+//! - `POST /documents` `{"text", ..}`，保留排版翻译整篇文档
+//! - `GET /providers` 可用的翻译接口
+//! - `GET /health`
+//!
+//! 请求中的`provider`为配置文件中的profile名称(见`config`模块)，未指定时使用默认profile。
+//! 配置文件中没有profile时，提供`cnki`、`baidu`两个接口，凭据由环境变量提供，如`TRANSLATION_BAIDU_APPID`
+//! (或0.1的`BAIDU_APPID`)。
+//! 配置文件(TOML):
+//! ```toml
+//! addr = "127.0.0.1:8080"
+//! cache = 10000          # 缓存条数，0为不缓存
+//! cache_ttl = 86400      # 缓存有效期(秒)
+//! default = "baidu"      # 请求未指定时使用的profile
+//!
+//! [profiles.baidu]
+//! provider = "baidu"
+//! appid = "${BAIDU_APPID}"
+//! secret_key = "${BAIDU_SECRET_KEY}"
+//! qps = 1.0
+//! ```
+//! `translation-server`即调用`run`。注册自己的接口(见`registry`)后调用`run`或`serve`，请求中即可使用该接口:
+//! ```no_run
+//! # use translation_api::{config::Config, registry, server::{self, ServerConfig}, Translation};
+//! # use translation_api::cnkis::CNKI;
+//! # fn in_house(_: Option<&str>) -> Box<dyn Translation<'_> + '_> { Box::new(CNKI::default_new()) }
+//! # fn main() -> Result<(), translation_api::err::TransError> {
+//! registry::register("in-house", in_house)?;
+//! let config = Config::load("server.toml")?;
+//! server::serve(&ServerConfig::load("server.toml")?, &config)?;
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    ffi::OsString,
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cache::{CacheKey, CachedTranslator, MemoryCache, TransCache},
+    config::{self, Config, Profile},
+    document::{self, DocsOptions, Document, ErrorPolicy, Segment},
+    err::{ErrInfoBuildle, TransError},
+    middleware::ProviderBuilder,
+    registry, Language, Translate, Translation,
+};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Response, Server};
+
+#[derive(Parser)]
+#[command(name = "translation-server", version, about = "翻译HTTP服务")]
+struct Cli {
+    /// 配置文件(TOML)
+    #[arg(long, short, env = config::CONFIG_ENV)]
+    config: Option<PathBuf>,
+    /// 监听地址，覆盖配置文件
+    #[arg(long)]
+    addr: Option<String>,
+    /// 处理请求的线程数，覆盖配置文件
+    #[arg(long)]
+    threads: Option<usize>,
+}
+
+/// 服务本身的设置，与profile写在同一个文件中
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// 监听地址
+    pub addr: String,
+    /// 处理请求的线程数
+    pub threads: usize,
+    /// 缓存条数，0为不缓存
+    pub cache: usize,
+    /// 缓存有效期(秒)
+    pub cache_ttl: Option<u64>,
+    /// 请求体的最大字节数
+    pub max_body: usize,
+    /// 批量、文档翻译时每个请求的并发数
+    pub workers: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            addr: "127.0.0.1:8080".to_owned(),
+            threads: 4,
+            cache: 10_000,
+            cache_ttl: None,
+            max_body: 1 << 20,
+            workers: 1,
+        }
+    }
+}
+
+/// 一个翻译接口。每个请求新建翻译器，缓存和限流在请求间共用
+struct Provider {
+    profile: Profile,
+    cache: Option<SharedCache>,
+}
+
+impl Provider {
+    fn create(&self) -> Result<Box<dyn Translation<'_> + '_>, TransError> {
+        let t = Translate::from_config(&self.profile)?;
+        Ok(match &self.cache {
+            Some(cache) => {
+                let cache = cache.clone();
+                ProviderBuilder::new(t)
+                    .with(move |t| {
+                        Box::new(CachedTranslator::with_cache(t, cache))
+                            as Box<dyn Translation<'_> + '_>
+                    })
+                    .build()
+            }
+            None => t,
+        })
+    }
+}
+
+/// 所有请求共用的内存缓存
+#[derive(Clone)]
+struct SharedCache(Arc<Mutex<MemoryCache>>);
+
+impl SharedCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryCache> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TransCache for SharedCache {
+    fn get(&mut self, key: &CacheKey) -> Result<Option<String>, TransError> {
+        Ok(self.lock().get(key))
+    }
+    fn put(&mut self, key: CacheKey, value: String) -> Result<(), TransError> {
+        self.lock().put(key, value);
+        Ok(())
+    }
+    fn remove(&mut self, key: &CacheKey) -> Result<(), TransError> {
+        self.lock().remove(key);
+        Ok(())
+    }
+    fn clear(&mut self) -> Result<(), TransError> {
+        self.lock().clear();
+        Ok(())
+    }
+}
+
+struct App {
+    providers: Vec<Provider>,
+    default: usize,
+    max_body: usize,
+    workers: usize,
+}
+
+/// 请求中的接口、语言、领域，未指定时使用默认值
+#[derive(Deserialize)]
+struct Options {
+    #[serde(alias = "profile")]
+    provider: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    domain: Option<String>,
+    /// 逐句检测语言，已是目标语言的句子原样返回，其余按检测到的语言翻译
+    #[serde(default)]
+    mixed: bool,
+}
+
+#[derive(Deserialize)]
+struct TextRequest {
+    text: String,
+    #[serde(flatten)]
+    options: Options,
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    texts: Vec<String>,
+    #[serde(flatten)]
+    options: Options,
+}
+
+#[derive(Serialize)]
+struct TextResponse<'a> {
+    translation: String,
+    provider: &'a str,
+}
+
+#[derive(Serialize)]
+struct BatchItem<'a> {
+    translation: Option<String>,
+    error: Option<ErrorBody>,
+    provider: &'a str,
+}
+
+#[derive(Serialize)]
+struct DocumentResponse {
+    translation: String,
+    /// 句子数
+    segments: usize,
+    /// 翻译失败的句子数，失败的位置为`TRANS_ERR`
+    failed: usize,
+}
+
+#[derive(Serialize)]
+struct ProviderInfo<'a> {
+    name: &'a str,
+    /// 接口名称，见`Translate::name`
+    provider: String,
+    configured: bool,
+    default: bool,
+    rate_limit: Option<f64>,
+    /// 支持的(源语言, 目标语言)，`null`为不限
+    pairs: Option<Vec<(Language, Language)>>,
+    /// 支持的领域，`null`为不限
+    domains: Option<Vec<String>>,
+    max_length: usize,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    /// 接口返回的错误码
+    code: Option<String>,
+}
+
+/// 处理结果: HTTP状态码和JSON响应体
+type Reply = (u16, Vec<u8>);
+
+impl ServerConfig {
+    /// 读取配置文件中服务本身的设置，文件中的profile见`Config::load`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TransError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| server_err("load()", format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&text)
+            .map_err(|e| server_err("load()", format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// 按命令行参数运行服务，`args`的第一项为程序名
+pub fn run<I, T>(args: I) -> ExitCode
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let cli = Cli::parse_from(args);
+    let loaded = match &cli.config {
+        Some(path) => ServerConfig::load(path).and_then(|s| Ok((s, Config::load(path)?))),
+        None => Ok((ServerConfig::default(), Config::default())),
+    };
+    let (mut server_config, config) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("translation-server: {}", e.to_string().trim());
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(addr) = cli.addr {
+        server_config.addr = addr;
+    }
+    if let Some(threads) = cli.threads {
+        server_config.threads = threads;
+    }
+    match serve(&server_config, &config) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("translation-server: {}", e.to_string().trim());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// 按设置启动服务，处理请求直到进程退出。
+/// `config`中没有profile时，提供`registry::names()`中的所有接口
+pub fn serve(server_config: &ServerConfig, config: &Config) -> Result<(), TransError> {
+    let app = App::new(server_config, config)?;
+    let server = Server::http(&server_config.addr).map_err(|e| {
+        TransError::IOError(
+            ErrInfoBuildle::new()
+                .model("Server")
+                .method("serve()")
+                .original(format!("{}: {}", server_config.addr, e))
+                .build(),
+        )
+    })?;
+    eprintln!("translation-server: listening on {}", server_config.addr);
+    thread::scope(|s| {
+        for _ in 0..server_config.threads.max(1) {
+            s.spawn(|| listen(&server, &app));
+        }
+    });
+    Ok(())
+}
+
+impl App {
+    /// 读取所有profile
+    fn new(server_config: &ServerConfig, config: &Config) -> Result<Self, TransError> {
+        let mut names = config.profile_names();
+        if names.is_empty() {
+            names = registry::names();
+        }
+        let providers = names
+            .iter()
+            .map(|name| {
+                let cache = (server_config.cache > 0).then(|| {
+                    let cache = MemoryCache::new(server_config.cache);
+                    let cache = match server_config.cache_ttl {
+                        Some(ttl) => cache.ttl(Duration::from_secs(ttl)),
+                        None => cache,
+                    };
+                    SharedCache(Arc::new(Mutex::new(cache)))
+                });
+                let provider = Provider {
+                    profile: config.profile(name)?,
+                    cache,
+                };
+                // 启动时检查配置的语言、领域是否支持
+                provider.create()?;
+                Ok(provider)
+            })
+            .collect::<Result<Vec<_>, TransError>>()?;
+        let default = match config.default_name() {
+            Some(name) => providers
+                .iter()
+                .position(|p| p.profile.name == name)
+                .ok_or_else(|| server_err("serve()", format!("没有该profile: {}", name)))?,
+            None => 0,
+        };
+        Ok(App {
+            providers,
+            default,
+            max_body: server_config.max_body,
+            workers: server_config.workers,
+        })
+    }
+}
+
+fn listen(server: &Server, app: &App) {
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json; charset=utf-8").unwrap();
+    while let Ok(mut request) = server.recv() {
+        let start = Instant::now();
+        let (status, body) = handle(app, &mut request);
+        eprintln!(
+            "{} {} {} {}ms",
+            request.method(),
+            request.url(),
+            status,
+            start.elapsed().as_millis()
+        );
+        let response = Response::new(
+            status.into(),
+            vec![content_type.clone()],
+            Cursor::new(body.as_slice()),
+            Some(body.len()),
+            None,
+        );
+        if let Err(e) = request.respond(response) {
+            eprintln!("translation-server: {}", e);
+        }
+    }
+}
+
+fn handle(app: &App, request: &mut tiny_http::Request) -> Reply {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    let method = request.method().clone();
+    match (method, path.trim_end_matches('/')) {
+        (Method::Get, "/health") => json(200, &serde_json::json!({"status": "ok"})),
+        (Method::Get, "/providers") => providers(app),
+        (Method::Post, path @ ("/translate" | "/translate/batch" | "/documents")) => {
+            let body = match read_body(request, app.max_body) {
+                Ok(body) => body,
+                Err(reply) => return reply,
+            };
+            match path {
+                "/translate" => translate(app, &body),
+                "/translate/batch" => batch(app, &body),
+                _ => documents(app, &body),
+            }
+        }
+        (_, "/health" | "/providers" | "/translate" | "/translate/batch" | "/documents") => {
+            error(405, "method not allowed")
+        }
+        _ => error(404, "not found"),
+    }
+}
+
+fn providers(app: &App) -> Reply {
+    let list: Vec<_> = app
+        .providers
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            // 配置已在启动时检查过
+            let capabilities = p.create().map(|t| t.capabilities()).ok();
+            ProviderInfo {
+                name: &p.profile.name,
+                provider: p.profile.provider.name().to_owned(),
+                configured: p.profile.has_credentials(),
+                default: i == app.default,
+                rate_limit: p
+                    .profile
+                    .rate_limit
+                    .as_ref()
+                    .map(|l| 1.0 / l.interval().as_secs_f64()),
+                pairs: capabilities.as_ref().and_then(|c| c.pairs.clone()),
+                domains: capabilities.as_ref().and_then(|c| {
+                    c.domains
+                        .as_ref()
+                        .map(|domains| domains.iter().map(|d| d.to_string()).collect())
+                }),
+                max_length: capabilities.map_or(0, |c| c.max_length),
+            }
+        })
+        .collect();
+    json(200, &list)
+}
+
+fn translate(app: &App, body: &str) -> Reply {
+    let request: TextRequest = match parse(body) {
+        Ok(request) => request,
+        Err(reply) => return reply,
+    };
+    let mut t = match app.translator(&request.options) {
+        Ok(t) => t,
+        Err(reply) => return reply,
+    };
+    t.set_words(&request.text);
+    match t.trans() {
+        Ok(translation) => json(
+            200,
+            &TextResponse {
+                translation,
+                provider: t.served_by(),
+            },
+        ),
+        Err(e) => trans_error(&e),
+    }
+}
+
+fn batch(app: &App, body: &str) -> Reply {
+    let request: BatchRequest = match parse(body) {
+        Ok(request) => request,
+        Err(reply) => return reply,
+    };
+    let sources: Vec<&str> = request.texts.iter().map(String::as_str).collect();
+    let mut t = match app.translator(&request.options) {
+        Ok(t) => t,
+        Err(reply) => return reply,
+    };
+    let outcomes =
+        document::translate_segments(t.as_mut(), &sources, &app.docs_options(&request.options));
+    let results: Vec<_> = outcomes
+        .into_iter()
+        .map(|o| match o.result {
+            Ok(translation) => BatchItem {
+                translation: Some(translation),
+                error: None,
+                provider: o.provider,
+            },
+            Err(e) => BatchItem {
+                translation: None,
+                error: Some(error_body(&e)),
+                provider: o.provider,
+            },
+        })
+        .collect();
+    json(200, &serde_json::json!({ "results": results }))
+}
+
+fn documents(app: &App, body: &str) -> Reply {
+    let request: TextRequest = match parse(body) {
+        Ok(request) => request,
+        Err(reply) => return reply,
+    };
+    let mut t = match app.translator(&request.options) {
+        Ok(t) => t,
+        Err(reply) => return reply,
+    };
+    let (skeleton, sources) = document::parse(&request.text);
+    let outcomes =
+        document::translate_segments(t.as_mut(), &sources, &app.docs_options(&request.options));
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    let policy = ErrorPolicy::default();
+    let segments: Vec<_> = outcomes
+        .into_iter()
+        .map(|o| Segment {
+            source: o.source,
+            translation: policy.resolve(o).ok().flatten().unwrap_or_default(),
+        })
+        .collect();
+    let count = segments.len();
+    json(
+        200,
+        &DocumentResponse {
+            translation: Document { skeleton, segments }.text(),
+            segments: count,
+            failed,
+        },
+    )
+}
+
+impl App {
+    /// 按请求选择接口并设置语言、领域
+    fn translator(&self, options: &Options) -> Result<Box<dyn Translation<'_> + '_>, Reply> {
+        let provider = match &options.provider {
+            Some(name) => self
+                .providers
+                .iter()
+                .find(|p| p.profile.name == *name)
+                .ok_or_else(|| error(400, &format!("不支持的接口: {}", name)))?,
+            None => &self.providers[self.default],
+        };
+        if !provider.profile.has_credentials() {
+            return Err(error(
+                400,
+                &format!("未配置接口凭据: {}", provider.profile.name),
+            ));
+        }
+        let invalid = |e: TransError| error(400, e.to_string().trim());
+        let mut t = provider.create().map_err(invalid)?;
+        if let Some(from) = &options.from {
+            t.set_from(from.parse().map_err(invalid)?)
+                .map_err(invalid)?;
+        }
+        if let Some(to) = &options.to {
+            t.set_to(to.parse().map_err(invalid)?).map_err(invalid)?;
+        }
+        if let Some(domain) = &options.domain {
+            t.set_domain(domain.parse().map_err(invalid)?)
+                .map_err(invalid)?;
+        }
+        Ok(t)
+    }
+    fn docs_options(&self, options: &Options) -> DocsOptions {
+        DocsOptions {
+            workers: self.workers,
+            detect_language: options.mixed,
+            passthrough_target: options.mixed,
+            ..Default::default()
+        }
+    }
+}
+
+fn read_body(request: &mut tiny_http::Request, max: usize) -> Result<String, Reply> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(max as u64 + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| error(400, &e.to_string()))?;
+    if body.len() > max {
+        return Err(error(413, "request body too large"));
+    }
+    Ok(body)
+}
+
+fn parse<'de, T: Deserialize<'de>>(body: &'de str) -> Result<T, Reply> {
+    serde_json::from_str(body).map_err(|e| error(400, &e.to_string()))
+}
+
+fn json(status: u16, body: &impl Serialize) -> Reply {
+    (status, serde_json::to_vec(body).unwrap_or_default())
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    json(
+        status,
+        &ErrorBody {
+            error: message.to_owned(),
+            code: None,
+        },
+    )
+}
+
+fn error_body(e: &TransError) -> ErrorBody {
+    ErrorBody {
+        error: e.to_string().trim().to_owned(),
+        code: e.code().map(str::to_owned),
+    }
+}
+
+fn server_err(method: &str, msg: String) -> TransError {
+    TransError::ConfigError(
+        ErrInfoBuildle::new()
+            .model("Server")
+            .method(method)
+            .original(msg)
+            .build(),
+    )
+}
+
+/// 熔断、超出预算为503，调用方的错误(见`TransError::is_client_error`)为400，接口失败为502，其他为500
+fn trans_error(e: &TransError) -> Reply {
+    let status = match e {
+        TransError::CircuitOpen(_) | TransError::BudgetExceeded(_) => 503,
+        _ if e.is_client_error() => 400,
+        _ if e.is_provider_failure() => 502,
+        _ => 500,
+    };
+    json(status, &error_body(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(e: TransError) -> u16 {
+        trans_error(&e).0
+    }
+
+    #[test]
+    fn error_status() {
+        let info = || ErrInfoBuildle::new().model("Test").build();
+        assert_eq!(status(TransError::CircuitOpen(info())), 503);
+        assert_eq!(status(TransError::BudgetExceeded(info())), 503);
+        assert_eq!(status(TransError::Unsupported(info())), 400);
+        assert_eq!(status(TransError::ConfigError(info())), 400);
+        assert_eq!(status(TransError::RequestError(info())), 502);
+        assert_eq!(status(TransError::BaiduError(info())), 502);
+        assert_eq!(status(TransError::IOError(info())), 500);
+
+        // Baidu在调用接口前检查原文和凭据
+        let mut baidu = Translate::Baidu.create();
+        assert_eq!(status(baidu.trans().unwrap_err()), 400);
+        baidu.set_words("hello");
+        assert_eq!(status(baidu.trans().unwrap_err()), 400);
+        assert_eq!(status(baidu.set_from(Language::Kor).unwrap_err()), 400);
+    }
+}
@@ -0,0 +1,183 @@
+//! 单元测试使用的翻译器，不访问网络
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    capability::{unsupported, Capabilities},
+    err::{ErrInfo, ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 译文为原文的大写。原文含`fail`时返回`TransError::RequestError`
+#[derive(Clone)]
+pub(crate) struct Mock {
+    words: String,
+    from: Language,
+    to: Language,
+    domain: DomainType,
+    max_length: usize,
+    name: &'static str,
+    /// 每个字符的耗时，用于打乱并发翻译完成的顺序
+    delay: Duration,
+    /// 声明的能力，`None`为不限。设置不支持的语言、领域时返回`TransError::Unsupported`
+    capabilities: Option<Capabilities>,
+    /// 每次翻译都返回的错误
+    error: Option<fn(ErrInfo) -> TransError>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl Mock {
+    pub(crate) fn new() -> Self {
+        Mock {
+            words: String::new(),
+            from: Language::En,
+            to: Language::Zh,
+            domain: DomainType::General,
+            max_length: 5000,
+            name: "Mock",
+            delay: Duration::ZERO,
+            capabilities: None,
+            error: None,
+            calls: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+    pub(crate) fn named(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+    pub(crate) fn with_delay(mut self, per_char: Duration) -> Self {
+        self.delay = per_char;
+        self
+    }
+    pub(crate) fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+    /// 每次翻译都返回`kind`类型的错误，如`TransError::CircuitOpen`
+    pub(crate) fn failing(mut self, kind: fn(ErrInfo) -> TransError) -> Self {
+        self.error = Some(kind);
+        self
+    }
+    /// `trans()`的调用次数，`fork()`出的翻译器共用
+    pub(crate) fn calls(&self) -> Arc<AtomicUsize> {
+        self.calls.clone()
+    }
+}
+
+impl<'a> Translation<'a> for Mock {
+    fn set_words(&mut self, words: &str) {
+        self.words = words.to_owned();
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        if !self.capabilities().supports_from(language) {
+            return Err(unsupported(
+                self.name,
+                format!("不支持的源语言: {}", language),
+            ));
+        }
+        self.from = language;
+        Ok(())
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        if !self.capabilities().supports_to(language) {
+            return Err(unsupported(
+                self.name,
+                format!("不支持的目标语言: {}", language),
+            ));
+        }
+        self.to = language;
+        Ok(())
+    }
+
+    fn set_appid(&mut self, _appid: &'a str) {}
+
+    fn set_secret_key(&mut self, _secret_key: &'a str) {}
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        if !self.capabilities().supports_domain(&domain) {
+            return Err(unsupported(self.name, format!("不支持的领域: {}", domain)));
+        }
+        self.domain = domain;
+        Ok(())
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.max_length = l;
+    }
+
+    fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    fn get_words(&self) -> &str {
+        &self.words
+    }
+
+    fn from(&self) -> Language {
+        self.from
+    }
+
+    fn to(&self) -> Language {
+        self.to
+    }
+
+    fn appid(&self) -> &'a str {
+        ""
+    }
+
+    fn secret_key(&self) -> &'a str {
+        ""
+    }
+
+    fn domain(&self) -> DomainType {
+        self.domain.clone()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.name
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+            .clone()
+            .unwrap_or_else(|| Capabilities::unrestricted(self.max_length))
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(self.clone())
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(self.delay * self.words.chars().count() as u32);
+        let kind = match self.error {
+            Some(kind) => kind,
+            None if self.words.to_lowercase().contains("fail") => TransError::RequestError,
+            None => return Ok(self.words.to_uppercase()),
+        };
+        Err(kind(
+            ErrInfoBuildle::new()
+                .model(self.name)
+                .method("trans()")
+                .original(self.words.clone())
+                .build(),
+        ))
+    }
+}
+
+/// 临时目录下本进程专用的文件路径，已存在时先删除
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("translation_api-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
@@ -0,0 +1,599 @@
+//! 翻译记忆库(Translation Memory)
+//!
+//! 读取TMX 1.4文件，翻译前先在记忆库中查找完全匹配或模糊匹配的句子，命中时直接使用人工审核过的译文。
+//! 接口新翻译的句子对可以导出为TMX，交给审校人员审核后再并入记忆库
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write as _,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::{
+    cache::normalize,
+    capability::Capabilities,
+    err::{ErrInfoBuildle, TransError},
+    DomainType, Language, Result, Translation,
+};
+
+/// 记忆库中的匹配结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct TmMatch {
+    /// 记忆库中的原文
+    pub source: String,
+    /// 记忆库中的译文
+    pub target: String,
+    /// 相似度，1.0为完全匹配
+    pub score: f64,
+}
+
+/// 接口新翻译、尚未审核的句子对
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewUnit {
+    pub from: String,
+    pub to: String,
+    pub source: String,
+    pub target: String,
+    /// 产生译文的翻译接口
+    pub provider: String,
+}
+
+/// 翻译记忆库
+#[derive(Debug, Default)]
+pub struct TranslationMemory {
+    /// 每个翻译单元: 语言 => 句子。语言见`lang_key`，如`zh-CN` => `zh`、`zh-TW` => `zh-hant`
+    units: Vec<HashMap<String, String>>,
+    /// (语言, 规范化后的句子) => 翻译单元下标
+    exact: HashMap<(String, String), Vec<usize>>,
+    /// 记忆库中出现过的语言
+    languages: BTreeSet<String>,
+    new_units: Vec<NewUnit>,
+}
+
+impl TranslationMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 读取TMX文件
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut tm = Self::new();
+        tm.merge_file(path)?;
+        Ok(tm)
+    }
+    /// 读取TMX文件并入当前记忆库，返回读取的翻译单元数
+    pub fn merge_file(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let xml = fs::read_to_string(path)
+            .map_err(|e| tmx_err("merge_file()", e.to_string(), path.display().to_string()))?;
+        self.merge_tmx(&xml)
+    }
+    /// 解析TMX内容并入当前记忆库，返回读取的翻译单元数
+    pub fn merge_tmx(&mut self, xml: &str) -> Result<usize> {
+        let mut reader = Reader::from_str(xml);
+        let mut count = 0;
+        let mut unit: Option<HashMap<String, String>> = None;
+        let mut header_lang = String::new();
+        let mut tuv_lang = String::new();
+        let mut seg: Option<String> = None;
+        // 行内标记(bpt、ept、ph、it、ut)中的是原格式代码，不属于句子
+        let mut inline_depth = 0;
+        loop {
+            let event = reader.read_event().map_err(|e| {
+                tmx_err(
+                    "merge_tmx()",
+                    e.to_string(),
+                    reader.buffer_position().to_string(),
+                )
+            })?;
+            match event {
+                Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"header" => {
+                    header_lang = lang_attr(&e, b"srclang").unwrap_or_default();
+                }
+                Event::Start(e) => match e.name().as_ref() {
+                    b"tu" => unit = Some(HashMap::default()),
+                    b"tuv" => {
+                        tuv_lang = lang_attr(&e, b"xml:lang")
+                            .or_else(|| lang_attr(&e, b"lang"))
+                            .unwrap_or_else(|| header_lang.clone())
+                    }
+                    b"seg" => seg = Some(String::new()),
+                    b"bpt" | b"ept" | b"ph" | b"it" | b"ut" if seg.is_some() => inline_depth += 1,
+                    _ => {}
+                },
+                Event::Text(t) if inline_depth == 0 => {
+                    if let Some(seg) = seg.as_mut() {
+                        let text = t.unescape().map_err(|e| {
+                            tmx_err(
+                                "merge_tmx()",
+                                e.to_string(),
+                                reader.buffer_position().to_string(),
+                            )
+                        })?;
+                        seg.push_str(&text);
+                    }
+                }
+                Event::CData(t) if inline_depth == 0 => {
+                    if let Some(seg) = seg.as_mut() {
+                        seg.push_str(&String::from_utf8_lossy(&t));
+                    }
+                }
+                Event::End(e) => match e.name().as_ref() {
+                    b"seg" => {
+                        if let (Some(unit), Some(seg)) = (unit.as_mut(), seg.take()) {
+                            if !tuv_lang.is_empty() && !seg.trim().is_empty() {
+                                unit.insert(tuv_lang.clone(), seg);
+                            }
+                        }
+                    }
+                    b"tu" => {
+                        if let Some(unit) = unit.take() {
+                            if unit.len() > 1 {
+                                self.insert(unit);
+                                count += 1;
+                            }
+                        }
+                    }
+                    b"bpt" | b"ept" | b"ph" | b"it" | b"ut" if inline_depth > 0 => {
+                        inline_depth -= 1
+                    }
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(count)
+    }
+    /// 添加一个句子对(视为已审核)
+    pub fn add(&mut self, from: &str, source: &str, to: &str, target: &str) {
+        let mut unit = HashMap::default();
+        unit.insert(lang_key(from), source.to_owned());
+        unit.insert(lang_key(to), target.to_owned());
+        self.insert(unit);
+    }
+    fn insert(&mut self, unit: HashMap<String, String>) {
+        let i = self.units.len();
+        for (lang, text) in &unit {
+            self.languages.insert(lang.to_owned());
+            self.exact
+                .entry((lang.to_owned(), normalize(text)))
+                .or_default()
+                .push(i);
+        }
+        self.units.push(unit);
+    }
+    /// 翻译单元数
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+    /// 查找`text`的译文。`from`为None时在所有语言中查找。
+    /// 先找完全匹配(忽略多余空白)，`threshold`小于1.0时再找相似度不低于`threshold`的最佳模糊匹配
+    pub fn lookup(
+        &self,
+        text: &str,
+        from: Option<&str>,
+        to: &str,
+        threshold: f64,
+    ) -> Option<TmMatch> {
+        let text = normalize(text);
+        let to = lang_key(to);
+        let from = from.map(lang_key);
+        let is_source = |lang: &str| lang != to && from.as_ref().is_none_or(|f| f == lang);
+
+        for lang in self.languages.iter().filter(|l| is_source(l)) {
+            let Some(units) = self.exact.get(&(lang.to_owned(), text.clone())) else {
+                continue;
+            };
+            for unit in units.iter().map(|i| &self.units[*i]) {
+                if let Some(target) = unit.get(&to) {
+                    return Some(TmMatch {
+                        source: unit[lang].to_owned(),
+                        target: target.to_owned(),
+                        score: 1.0,
+                    });
+                }
+            }
+        }
+        if threshold >= 1.0 {
+            return None;
+        }
+
+        let text: Vec<char> = text.to_lowercase().chars().collect();
+        let mut best: Option<TmMatch> = None;
+        for unit in &self.units {
+            let Some(target) = unit.get(&to) else {
+                continue;
+            };
+            for (lang, source) in unit {
+                if !is_source(lang) {
+                    continue;
+                }
+                let candidate: Vec<char> = normalize(source).to_lowercase().chars().collect();
+                // 长度差过大时相似度不可能达到阈值
+                let longest = text.len().max(candidate.len()).max(1) as f64;
+                if text.len().abs_diff(candidate.len()) as f64 / longest > 1.0 - threshold {
+                    continue;
+                }
+                let score = 1.0 - levenshtein(&text, &candidate) as f64 / longest;
+                if score >= threshold && best.as_ref().is_none_or(|b| score > b.score) {
+                    best = Some(TmMatch {
+                        source: source.to_owned(),
+                        target: target.to_owned(),
+                        score,
+                    });
+                }
+            }
+        }
+        best
+    }
+    /// 记录接口新翻译的句子对，等待审核
+    pub fn record_new(&mut self, unit: NewUnit) {
+        if !self.new_units.contains(&unit) {
+            self.new_units.push(unit);
+        }
+    }
+    /// 接口新翻译、尚未审核的句子对
+    pub fn new_units(&self) -> &[NewUnit] {
+        &self.new_units
+    }
+    /// 把新翻译的句子对导出为TMX 1.4文件，返回导出的条数
+    pub fn export_new(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        fs::write(path, self.new_units_tmx())
+            .map_err(|e| tmx_err("export_new()", e.to_string(), path.display().to_string()))?;
+        Ok(self.new_units.len())
+    }
+    /// 新翻译的句子对，TMX 1.4格式
+    pub fn new_units_tmx(&self) -> String {
+        let srclang = match self.new_units.first() {
+            Some(u) if self.new_units.iter().all(|n| n.from == u.from) => u.from.as_str(),
+            _ => "*all*",
+        };
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tmx version=\"1.4\">\n");
+        let _ = writeln!(
+            xml,
+            "  <header creationtool=\"translation_api\" creationtoolversion=\"{}\" segtype=\"sentence\" o-tmf=\"translation_api\" adminlang=\"en\" srclang=\"{}\" datatype=\"plaintext\"/>",
+            env!("CARGO_PKG_VERSION"),
+            escape(srclang)
+        );
+        xml.push_str("  <body>\n");
+        for u in &self.new_units {
+            xml.push_str("    <tu>\n");
+            let _ = writeln!(
+                xml,
+                "      <prop type=\"x-provider\">{}</prop>",
+                escape(&u.provider)
+            );
+            for (lang, seg) in [(&u.from, &u.source), (&u.to, &u.target)] {
+                let _ = writeln!(
+                    xml,
+                    "      <tuv xml:lang=\"{}\"><seg>{}</seg></tuv>",
+                    escape(lang),
+                    escape(seg.trim())
+                );
+            }
+            xml.push_str("    </tu>\n");
+        }
+        xml.push_str("  </body>\n</tmx>\n");
+        xml
+    }
+}
+
+/// 翻译前先查记忆库的翻译器。未命中时调用被包装的接口，并把新译文记录到记忆库的待审核列表
+/// `build()`/`fork()`出的翻译器与原翻译器共用同一个记忆库
+pub struct TmTranslator<'a> {
+    inner: Box<dyn Translation<'a> + 'a>,
+    tm: Arc<Mutex<TranslationMemory>>,
+    threshold: f64,
+    last: Option<TmMatch>,
+}
+
+impl<'a> TmTranslator<'a> {
+    /// 默认只使用完全匹配
+    pub fn new(inner: Box<dyn Translation<'a> + 'a>, tm: TranslationMemory) -> Self {
+        TmTranslator {
+            inner,
+            tm: Arc::new(Mutex::new(tm)),
+            threshold: 1.0,
+            last: None,
+        }
+    }
+    /// 模糊匹配的相似度阈值(0.0~1.0)，按字符的编辑距离计算。医学文本建议不低于0.9
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+    /// 记忆库，用于导出新译文等
+    pub fn memory(&self) -> MutexGuard<'_, TranslationMemory> {
+        self.tm.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    /// 上一次`trans()`命中的记忆库条目，未命中时为None
+    pub fn last_match(&self) -> Option<&TmMatch> {
+        self.last.as_ref()
+    }
+}
+
+impl<'a> Translation<'a> for TmTranslator<'a> {
+    fn set_words(&mut self, words: &str) {
+        self.inner.set_words(words)
+    }
+
+    fn set_from(&mut self, language: Language) -> Result<()> {
+        self.inner.set_from(language)
+    }
+
+    fn set_to(&mut self, language: Language) -> Result<()> {
+        self.inner.set_to(language)
+    }
+
+    fn set_appid(&mut self, appid: &'a str) {
+        self.inner.set_appid(appid)
+    }
+
+    fn set_secret_key(&mut self, secret_key: &'a str) {
+        self.inner.set_secret_key(secret_key)
+    }
+
+    fn set_domain(&mut self, domain: DomainType) -> Result<()> {
+        self.inner.set_domain(domain)
+    }
+
+    fn set_max_length(&mut self, l: usize) {
+        self.inner.set_max_length(l)
+    }
+
+    fn max_length(&self) -> usize {
+        self.inner.max_length()
+    }
+
+    fn get_words(&self) -> &str {
+        self.inner.get_words()
+    }
+
+    fn from(&self) -> Language {
+        self.inner.from()
+    }
+
+    fn to(&self) -> Language {
+        self.inner.to()
+    }
+
+    fn appid(&self) -> &'a str {
+        self.inner.appid()
+    }
+
+    fn secret_key(&self) -> &'a str {
+        self.inner.secret_key()
+    }
+
+    fn domain(&self) -> DomainType {
+        self.inner.domain()
+    }
+
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn served_by(&self) -> &'static str {
+        self.inner.served_by()
+    }
+
+    fn build(&self) -> Box<dyn Translation<'a> + 'a> {
+        Box::new(TmTranslator {
+            inner: self.inner.build(),
+            tm: self.tm.clone(),
+            threshold: self.threshold,
+            last: None,
+        })
+    }
+
+    fn trans(&mut self) -> Result<String> {
+        self.last = None;
+        let words = self.inner.get_words().to_owned();
+        let from = self.inner.from().tag();
+        let Some(to) = self.inner.to().tag() else {
+            return self.inner.trans();
+        };
+        let hit = self.memory().lookup(&words, from, to, self.threshold);
+        if let Some(m) = hit {
+            let target = m.target.clone();
+            self.last = Some(m);
+            return Ok(target);
+        }
+        let res = self.inner.trans()?;
+        self.memory().record_new(NewUnit {
+            from: from.unwrap_or("und").to_owned(),
+            to: to.to_owned(),
+            source: words.trim().to_owned(),
+            target: res.trim().to_owned(),
+            provider: self.inner.served_by().to_owned(),
+        });
+        Ok(res)
+    }
+}
+
+/// 比较用的语言标签，小写。能解析为`Language`的使用其BCP 47标签，保留简繁之分:
+/// `zh-CN` => `zh`, `zh-TW` => `zh-hant`, `EN_us` => `en`；其余只取主标签
+pub(crate) fn lang_key(lang: &str) -> String {
+    match lang.parse::<Language>().ok().and_then(Language::tag) {
+        Some(tag) => tag.to_lowercase(),
+        None => lang
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase(),
+    }
+}
+
+fn lang_attr(e: &BytesStart<'_>, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name)
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| lang_key(&v))
+}
+
+/// 按字符计算的编辑距离
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+fn tmx_err(method: &str, err: String, data: String) -> TransError {
+    TransError::IOError(
+        ErrInfoBuildle::new()
+            .model("TMX")
+            .method(method)
+            .original(err)
+            .data(data)
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pivot::PivotTranslator, testing::Mock};
+
+    #[test]
+    fn lang_keys() {
+        assert_eq!(lang_key("zh-CN"), "zh");
+        assert_eq!(lang_key("ZH_cn"), "zh");
+        assert_eq!(lang_key("zh-TW"), "zh-hant");
+        assert_eq!(lang_key("zh-Hant"), "zh-hant");
+        assert_eq!(lang_key("EN_us"), "en");
+        assert_eq!(lang_key("jpn"), "ja");
+        // 不认识的语言只取主标签
+        assert_eq!(lang_key("x-klingon"), "x");
+        assert_eq!(lang_key("und"), "und");
+    }
+
+    #[test]
+    fn keeps_simplified_and_traditional_apart() {
+        let mut tm = TranslationMemory::new();
+        tm.add("en-US", "Liver", "zh-CN", "肝脏");
+        tm.add("en", "Liver", "zh-TW", "肝臟");
+        assert_eq!(tm.lookup("liver", None, "zh", 1.0), None);
+        assert_eq!(
+            tm.lookup("Liver", Some("en"), "zh", 1.0).unwrap().target,
+            "肝脏"
+        );
+        assert_eq!(
+            tm.lookup("Liver", Some("en"), "zh-Hant", 1.0)
+                .unwrap()
+                .target,
+            "肝臟"
+        );
+    }
+
+    #[test]
+    fn fuzzy_lookup() {
+        let mut tm = TranslationMemory::new();
+        tm.add("en", "The patient has a fever.", "zh", "患者发热。");
+        let hit = tm
+            .lookup("The  patient has a fever. ", Some("en"), "zh", 1.0)
+            .unwrap();
+        assert_eq!(hit.score, 1.0);
+        assert_eq!(
+            tm.lookup("The patient has fever.", Some("en"), "zh", 1.0),
+            None
+        );
+        let hit = tm
+            .lookup("The patient has fever.", Some("en"), "zh", 0.8)
+            .unwrap();
+        assert!(hit.score >= 0.8 && hit.score < 1.0);
+        assert_eq!(hit.target, "患者发热。");
+        assert_eq!(tm.lookup("Something else.", Some("en"), "zh", 0.8), None);
+    }
+
+    #[test]
+    fn tmx_round_trip() {
+        let mut tm = TranslationMemory::new();
+        tm.record_new(NewUnit {
+            from: "en".to_owned(),
+            to: "zh-Hant".to_owned(),
+            source: "A & B <c>".to_owned(),
+            target: "甲 & 乙".to_owned(),
+            provider: "Mock".to_owned(),
+        });
+        let xml = tm.new_units_tmx();
+        let mut other = TranslationMemory::new();
+        assert_eq!(other.merge_tmx(&xml).unwrap(), 1);
+        assert_eq!(
+            other
+                .lookup("A & B <c>", Some("en"), "zh-TW", 1.0)
+                .unwrap()
+                .target,
+            "甲 & 乙"
+        );
+    }
+
+    #[test]
+    fn tmx_skips_inline_markup() {
+        let xml = r#"<tmx version="1.4"><header srclang="en-US"/><body>
+            <tu><tuv><seg>Take <bpt i="1">&lt;b&gt;</bpt>two<ept i="1">&lt;/b&gt;</ept> tablets.</seg></tuv>
+                <tuv xml:lang="zh-CN"><seg>服用两片。</seg></tuv></tu>
+            <tu><tuv xml:lang="en"><seg>Only one language.</seg></tuv></tu>
+        </body></tmx>"#;
+        let mut tm = TranslationMemory::new();
+        assert_eq!(tm.merge_tmx(xml).unwrap(), 1);
+        assert_eq!(
+            tm.lookup("Take two tablets.", Some("en"), "zh", 1.0)
+                .unwrap()
+                .target,
+            "服用两片。"
+        );
+        assert!(tm.merge_tmx("<tmx><body><tu>").is_ok());
+        assert!(tm.merge_tmx("<tmx></body>").is_err());
+    }
+
+    #[test]
+    fn records_serving_provider() {
+        let mut tm = TranslationMemory::new();
+        tm.add("en", "hello", "zh", "你好");
+        let pivot = PivotTranslator::new(
+            Box::new(Mock::new().named("First")),
+            Box::new(Mock::new().named("Second")),
+            Language::En,
+        )
+        .unwrap();
+        let mut t = TmTranslator::new(Box::new(pivot), tm);
+        t.set_from(Language::En).unwrap();
+        t.set_to(Language::Zh).unwrap();
+        t.set_words("hello");
+        assert_eq!(t.trans().unwrap(), "你好");
+        assert_eq!(t.last_match().unwrap().score, 1.0);
+        t.set_words("new words");
+        assert_eq!(t.trans().unwrap(), "NEW WORDS");
+        assert!(t.last_match().is_none());
+        let memory = t.memory();
+        let units = memory.new_units();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].provider, "Second");
+        assert_eq!((units[0].from.as_str(), units[0].to.as_str()), ("en", "zh"));
+    }
+}